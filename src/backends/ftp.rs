@@ -1,103 +1,466 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
 use std::path::Path;
-use std::str::FromStr;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{ready, Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime};
 
 use async_trait::async_trait;
-use suppaftp::AsyncNativeTlsFtpStream;
-use tokio::sync::Mutex;
+use chrono::NaiveDateTime;
+use suppaftp::async_native_tls::{Certificate, TlsConnector};
+use suppaftp::list::{File as ListFile, PosixPexQuery};
+use suppaftp::types::FileType as TransferType;
+use suppaftp::{AsyncNativeTlsConnector, AsyncNativeTlsFtpStream, FtpError, Mode, Status};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::runtime::Handle;
+use tokio::sync::{Mutex, MutexGuard, OnceCell, OwnedMutexGuard};
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt, FuturesAsyncWriteCompatExt};
 
-use crate::data::{File, FileType, Metadata};
-use crate::error::{Error, Result};
+use crate::backends::reconnect::ReconnectPolicy;
+use crate::data::{Capabilities, File, FileType, Metadata};
+use crate::error::{Error, Result, ResultExt};
 use crate::util::remove_lowest_path_item;
-use crate::FSBackend;
+use crate::{FSBackend, FileReader, FileWriter};
 
 pub type FTPBackendStream = AsyncNativeTlsFtpStream;
 
 pub struct FTPBackend {
-    pub stream: Mutex<FTPBackendStream>,
+    pub stream: Arc<Mutex<FTPBackendStream>>,
+    mlsd_supported: OnceCell<bool>,
+    // Only present when the backend was built by `FTPBuilder`, which allows reconnecting
+    builder: Option<FTPBuilder>,
+    connection_lost: Arc<AtomicBool>,
+    // When a command was last sent, which the keepalive measures idleness from
+    last_activity: Arc<std::sync::Mutex<Instant>>,
 }
 
 impl FTPBackend {
+    pub(crate) const CAPABILITIES: Capabilities = Capabilities::ATOMIC_RENAME
+        .union(Capabilities::RANGED_READS)
+        .union(Capabilities::APPEND)
+        .union(Capabilities::MODIFIED_TIME);
+
     pub fn new(stream: FTPBackendStream) -> Self {
         Self {
-            stream: Mutex::new(stream),
+            stream: Arc::new(Mutex::new(stream)),
+            mlsd_supported: OnceCell::new(),
+            builder: None,
+            connection_lost: Arc::new(AtomicBool::new(false)),
+            last_activity: Arc::new(std::sync::Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn connect(host: impl Into<String>) -> FTPBuilder {
+        FTPBuilder {
+            host: host.into(),
+            port: None,
+            user: "anonymous".to_string(),
+            password: "anonymous".to_string(),
+            security: FTPSecurity::Plain,
+            mode: Mode::Passive,
+            root_certificates: vec![],
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            reconnect: ReconnectPolicy::default(),
+            keepalive: None,
         }
     }
 
     pub fn inner(&mut self) -> &mut FTPBackendStream {
-        self.stream.get_mut()
+        Arc::get_mut(&mut self.stream)
+            .expect("FTP stream is still held by an open transfer")
+            .get_mut()
     }
 
     pub fn unwrap(self) -> FTPBackendStream {
-        self.stream.into_inner()
+        Arc::try_unwrap(self.stream)
+            .ok()
+            .expect("FTP stream is still held by an open transfer")
+            .into_inner()
+    }
+
+    async fn lock(&self) -> Result<MutexGuard<'_, FTPBackendStream>> {
+        let mut stream = self.stream.lock().await;
+        self.ensure_connected(&mut stream).await?;
+        *self.last_activity.lock().unwrap() = Instant::now();
+        Ok(stream)
+    }
+
+    async fn lock_owned(&self) -> Result<OwnedMutexGuard<FTPBackendStream>> {
+        let mut stream = self.stream.clone().lock_owned().await;
+        self.ensure_connected(&mut stream).await?;
+        *self.last_activity.lock().unwrap() = Instant::now();
+        Ok(stream)
+    }
+
+    // The writer holds on to the stream itself instead of borrowing the backend, which lets the
+    // pool hand it out together with the connection
+    pub(crate) async fn open_owned_write(
+        &self,
+        path: &str,
+        overwrite: bool,
+    ) -> Result<FileWriter<'static>> {
+        if !overwrite && self.exists(path).await? {
+            return Err(Error::FileAlreadyExists(path.to_string()).context("open_write", &[path]));
+        }
+
+        let mut stream = self.lock_owned().await.context("open_write", &[path])?;
+        let data_stream = stream
+            .put_with_stream(path)
+            .await
+            .context("open_write", &[path])?;
+
+        Ok(Box::new(FTPWriter {
+            path: path.to_string(),
+            transfer: Some((stream, data_stream.compat_write())),
+            finalize: None,
+        }))
+    }
+
+    fn reconnecting_builder(&self) -> Option<&FTPBuilder> {
+        self.builder
+            .as_ref()
+            .filter(|builder| builder.reconnect.max_attempts > 0)
+    }
+
+    async fn ensure_connected(&self, stream: &mut FTPBackendStream) -> Result<()> {
+        let Some(builder) = self.reconnecting_builder() else {
+            return Ok(());
+        };
+
+        if self.connection_lost.load(Ordering::Acquire) || is_closed(stream).await {
+            *stream = builder
+                .reconnect
+                .reconnect(|| builder.connect_stream())
+                .await?;
+            self.connection_lost.store(false, Ordering::Release);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FTPSecurity {
+    Plain,
+    // Upgrades the control connection with AUTH TLS after connecting
+    ExplicitTls,
+    // Speaks TLS from the start, usually on port 990
+    ImplicitTls,
+}
+
+#[derive(Clone)]
+pub struct FTPBuilder {
+    host: String,
+    port: Option<u16>,
+    user: String,
+    password: String,
+    security: FTPSecurity,
+    mode: Mode,
+    root_certificates: Vec<Certificate>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    reconnect: ReconnectPolicy,
+    keepalive: Option<Duration>,
+}
+
+impl FTPBuilder {
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn login(mut self, user: impl Into<String>, password: impl Into<String>) -> Self {
+        self.user = user.into();
+        self.password = password.into();
+        self
+    }
+
+    pub fn security(mut self, security: FTPSecurity) -> Self {
+        self.security = security;
+        self
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // Trusts certificates signed by `certificate` in addition to the system roots
+    pub fn root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    pub fn accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    pub fn accept_invalid_hostnames(mut self, accept_invalid_hostnames: bool) -> Self {
+        self.accept_invalid_hostnames = accept_invalid_hostnames;
+        self
+    }
+
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    // Sends NOOP whenever the connection has been idle for `interval`
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    pub async fn build(self) -> Result<FTPBackend> {
+        let backend = FTPBackend {
+            builder: Some(self.clone()),
+            ..FTPBackend::new(self.connect_stream().await?)
+        };
+
+        if let Some(interval) = self.keepalive {
+            tokio::spawn(keepalive(
+                Arc::downgrade(&backend.stream),
+                backend.connection_lost.clone(),
+                backend.last_activity.clone(),
+                interval,
+            ));
+        }
+
+        Ok(backend)
+    }
+
+    async fn connect_stream(&self) -> Result<FTPBackendStream> {
+        let port = self.port.unwrap_or(match self.security {
+            FTPSecurity::ImplicitTls => 990,
+            _ => 21,
+        });
+        let address = (self.host.as_str(), port);
+
+        let mut stream = match self.security {
+            FTPSecurity::Plain => FTPBackendStream::connect(address).await?,
+            FTPSecurity::ExplicitTls => {
+                FTPBackendStream::connect(address)
+                    .await?
+                    .into_secure(self.tls_connector(), &self.host)
+                    .await?
+            }
+            FTPSecurity::ImplicitTls => {
+                FTPBackendStream::connect_secure_implicit(address, self.tls_connector(), &self.host)
+                    .await?
+            }
+        };
+        stream.set_mode(self.mode);
+
+        match stream.login(&self.user, &self.password).await {
+            Err(FtpError::UnexpectedResponse(response))
+                if response.status == Status::NotLoggedIn =>
+            {
+                return Err(Error::AuthenticationFailed(self.user.clone()));
+            }
+            result => result?,
+        }
+        stream.transfer_type(TransferType::Binary).await?;
+
+        Ok(stream)
+    }
+
+    fn tls_connector(&self) -> AsyncNativeTlsConnector {
+        let mut connector = TlsConnector::new()
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .danger_accept_invalid_hostnames(self.accept_invalid_hostnames);
+
+        for certificate in &self.root_certificates {
+            connector = connector.add_root_certificate(certificate.clone());
+        }
+
+        connector.into()
     }
 }
 
 #[async_trait]
 impl FSBackend for FTPBackend {
+    fn capabilities(&self) -> Capabilities {
+        Self::CAPABILITIES
+    }
+
     async fn disconnect(&self) -> Result<()> {
         self.stream.lock().await.quit().await?;
         Ok(())
     }
     async fn exists(&self, path: &str) -> Result<bool> {
-        todo!()
+        Ok(
+            stat(&mut *self.lock().await.context("exists", &[path])?, path)
+                .await
+                .context("exists", &[path])?
+                .is_some(),
+        )
     }
 
     async fn get_file_type(&self, path: &str) -> Result<FileType> {
-        todo!()
+        stat(
+            &mut *self.lock().await.context("get_file_type", &[path])?,
+            path,
+        )
+        .await
+        .context("get_file_type", &[path])?
+        .map(|metadata| metadata.r#type)
+        .ok_or_else(|| Error::FileNonexistent(path.to_string()))
+        .context("get_file_type", &[path])
     }
 
-    async fn retrieve_files(&self, paths: &[&str]) -> Result<Vec<File>> {
-        let mut stream = self.stream.lock().await;
+    // FTP has no notion of following symlinks, entries are reported however the server sees them
+    async fn retrieve_files(&self, paths: &[&str], _follow_symlinks: bool) -> Result<Vec<File>> {
+        let mut stream = self.lock().await.context("retrieve_files", paths)?;
         let mut files = vec![];
 
         for path in paths {
-            files.push(stream.mlst(Some(&path)).await?)
+            let path_std = Path::new(path);
+
+            files.push(File {
+                path: path.to_string(),
+                name: path_std
+                    .file_name()
+                    .ok_or(Error::NoFileName)
+                    .context("retrieve_files", &[path])?
+                    .to_str()
+                    .unwrap() // Input paths are already Unicode
+                    .to_string(),
+                extension: path_std
+                    .extension()
+                    .map(|os_str| os_str.to_str().unwrap().to_lowercase()), // Input paths are already Unicode
+                metadata: stat(&mut stream, path)
+                    .await
+                    .context("retrieve_files", &[path])?
+                    .ok_or_else(|| Error::FileNonexistent(path.to_string()))
+                    .context("retrieve_files", &[path])?,
+            })
         }
 
-        println!("{:?}", files);
+        Ok(files)
+    }
 
-        Ok(vec![])
+    async fn symlink_metadata(&self, path: &str) -> Result<Metadata> {
+        stat(
+            &mut *self.lock().await.context("symlink_metadata", &[path])?,
+            path,
+        )
+        .await
+        .context("symlink_metadata", &[path])?
+        .ok_or_else(|| Error::FileNonexistent(path.to_string()))
+        .context("symlink_metadata", &[path])
     }
 
     async fn retrieve_file_content(&self, path: &str) -> Result<Vec<u8>> {
-        todo!()
+        let mut stream = self
+            .lock()
+            .await
+            .context("retrieve_file_content", &[path])?;
+        let result = stream.retr_as_stream(path).await;
+        let mut data_stream = check_missing(&mut stream, result, path)
+            .await
+            .context("retrieve_file_content", &[path])?
+            .compat();
+
+        let mut contents = vec![];
+        data_stream
+            .read_to_end(&mut contents)
+            .await
+            .context("retrieve_file_content", &[path])?;
+        // Some servers only report a missing file once the transfer has started
+        let result = stream.finalize_retr_stream(data_stream.into_inner()).await;
+        check_missing(&mut stream, result, path)
+            .await
+            .context("retrieve_file_content", &[path])?;
+
+        Ok(contents)
     }
 
     async fn read_dir(&self, path: &str) -> Result<Vec<File>> {
-        Ok(self
-            .stream
-            .lock()
-            .await
-            .list(Some(path))
-            .await?
-            .into_iter()
-            .filter_map(|file_str| suppaftp::list::File::from_str(&file_str).ok())
-            .map(|file| {
-                let path = format!("{path}/{}", file.name());
-                let extension = Path::new(&path)
-                    .extension()
-                    .and_then(|os_str| os_str.to_str().and_then(|str| Some(str.to_lowercase())));
-
-                File {
-                    path,
-                    name: file.name().to_string(),
-                    extension,
-                    metadata: Metadata {
-                        r#type: FileType::from_bools(
-                            file.is_file(),
-                            file.is_directory(),
-                            file.is_symlink(),
-                        ),
-                        modified: Some(file.modified()),
-                        accessed: None,
-                        created: None,
-                        size: Some(file.size() as u64),
-                        readonly: false, // FIXME: Assumption
-                        unix_mode: None,
-                    },
+        let mut stream = self.lock().await.context("read_dir", &[path])?;
+        let mlsd_supported = *self
+            .mlsd_supported
+            .get_or_try_init(|| supports_mlsd(&mut stream))
+            .await?;
+
+        if mlsd_supported {
+            let mut files = vec![];
+
+            let result = stream.mlsd(Some(path)).await;
+            for line in check_missing(&mut stream, result, path)
+                .await
+                .context("read_dir", &[path])?
+            {
+                let (facts, name) = parse_mlst_line(line.trim_end_matches(['\r', '\n']));
+
+                // Skip the entries for the listed directory itself and its parent
+                if facts
+                    .get("type")
+                    .is_some_and(|r#type| matches!(r#type.to_lowercase().as_str(), "cdir" | "pdir"))
+                {
+                    continue;
                 }
-            })
-            .collect())
+
+                files.push(file_in_dir(path, name, metadata_from_mlst_facts(&facts)));
+            }
+
+            return Ok(files);
+        }
+
+        let mut files = vec![];
+
+        let result = stream.list(Some(path)).await;
+        for line in check_missing(&mut stream, result, path)
+            .await
+            .context("read_dir", &[path])?
+        {
+            let Some(ListEntry {
+                file,
+                unix_mode,
+                link_count,
+            }) = parse_list_line(&line).context("read_dir", &[path])?
+            else {
+                continue;
+            };
+
+            let (user, group) = if unix_mode.is_some() {
+                owner_names_from_list_line(&line)
+            } else {
+                (None, None)
+            };
+
+            let metadata = Metadata {
+                r#type: FileType::from_bools(
+                    file.is_file(),
+                    file.is_directory(),
+                    file.is_symlink(),
+                ),
+                modified: Some(file.modified()),
+                accessed: None,
+                created: None,
+                size: Some(file.size() as u64),
+                readonly: unix_mode.is_some() && !file.can_write(PosixPexQuery::Owner),
+                unix_mode: unix_mode.map(|mode| mode.into()),
+                link_target: file
+                    .symlink()
+                    .map(|target| target.to_string_lossy().into_owned()),
+                link_count,
+                inode: None,
+                device: None,
+                uid: file.uid(),
+                gid: file.gid(),
+                user,
+                group,
+            };
+
+            files.push(file_in_dir(path, file.name(), metadata));
+        }
+
+        Ok(files)
     }
 
     async fn create_file(
@@ -107,20 +470,39 @@ impl FSBackend for FTPBackend {
         contents: Option<&[u8]>,
     ) -> Result<()> {
         if !overwrite && self.exists(path).await? {
-            return Err(Error::FileAlreadyExists(path.to_string()));
+            return Err(Error::FileAlreadyExists(path.to_string()).context("create_file", &[path]));
         }
 
-        self.stream
-            .lock()
+        let mut stream = self.lock().await.context("create_file", &[path])?;
+        let result = stream.put_file(path, &mut contents.unwrap_or(&[])).await;
+        check_missing(&mut stream, result, parent_dir(path))
             .await
-            .put_file(path, &mut contents.unwrap_or(&[]))
-            .await?;
+            .context("create_file", &[path])?;
+
+        Ok(())
+    }
+
+    async fn append_file(&self, path: &str, contents: &[u8]) -> Result<()> {
+        let mut stream = self.lock().await.context("append_file", &[path])?;
+        let result = stream.append_file(path, &mut &contents[..]).await;
+        check_missing(&mut stream, result, parent_dir(path))
+            .await
+            .context("append_file", &[path])?;
 
         Ok(())
     }
 
     async fn create_dir(&self, path: &str) -> Result<()> {
-        self.stream.lock().await.mkdir(path).await?;
+        // Servers disagree on the reply code for an existing directory, so check beforehand
+        if self.exists(path).await? {
+            return Err(Error::FileAlreadyExists(path.to_string()).context("create_dir", &[path]));
+        }
+
+        let mut stream = self.lock().await.context("create_dir", &[path])?;
+        let result = stream.mkdir(path).await;
+        check_missing(&mut stream, result, parent_dir(path))
+            .await
+            .context("create_dir", &[path])?;
         Ok(())
     }
 
@@ -128,19 +510,31 @@ impl FSBackend for FTPBackend {
         let new_path = format!("{}/{new_name}", remove_lowest_path_item(path));
 
         if !overwrite && self.exists(&new_path).await? {
-            return Err(Error::FileAlreadyExists(new_path));
+            return Err(Error::FileAlreadyExists(new_path.clone())
+                .context("rename_file", &[path, &new_path]));
         }
 
-        self.stream.lock().await.rename(path, &new_path).await?;
+        let mut stream = self
+            .lock()
+            .await
+            .context("rename_file", &[path, &new_path])?;
+        let result = stream.rename(path, &new_path).await;
+        check_missing(&mut stream, result, path)
+            .await
+            .context("rename_file", &[path, &new_path])?;
         Ok(())
     }
 
     async fn move_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
         if !overwrite && self.exists(to).await? {
-            return Err(Error::FileAlreadyExists(to.to_string()));
+            return Err(Error::FileAlreadyExists(to.to_string()).context("move_file", &[from, to]));
         }
 
-        self.stream.lock().await.rename(from, to).await?;
+        let mut stream = self.lock().await.context("move_file", &[from, to])?;
+        let result = stream.rename(from, to).await;
+        check_missing(&mut stream, result, from)
+            .await
+            .context("move_file", &[from, to])?;
         Ok(())
     }
 
@@ -151,12 +545,27 @@ impl FSBackend for FTPBackend {
     }
 
     async fn remove_file(&self, path: &str) -> Result<()> {
-        self.stream.lock().await.rm(path).await?;
+        let mut stream = self.lock().await.context("remove_file", &[path])?;
+        let result = stream.rm(path).await;
+        check_missing(&mut stream, result, path)
+            .await
+            .context("remove_file", &[path])?;
         Ok(())
     }
 
     async fn remove_dir(&self, path: &str) -> Result<()> {
-        self.stream.lock().await.rmdir(path).await?;
+        let mut stream = self.lock().await.context("remove_dir", &[path])?;
+        let result = stream.rmdir(path).await;
+
+        // The reply code does not tell a directory that still has entries apart either
+        if is_file_unavailable(&result)
+            && !stream.nlst(Some(path)).await.unwrap_or_default().is_empty()
+        {
+            return Err(io::ErrorKind::DirectoryNotEmpty).context("remove_dir", &[path]);
+        }
+        check_missing(&mut stream, result, path)
+            .await
+            .context("remove_dir", &[path])?;
         Ok(())
     }
 
@@ -170,4 +579,568 @@ impl FSBackend for FTPBackend {
             "FTP".into(),
         ))
     }
+
+    async fn open_write(&self, path: &str, overwrite: bool) -> Result<FileWriter> {
+        self.open_owned_write(path, overwrite).await
+    }
+
+    async fn open_read(&self, path: &str) -> Result<FileReader> {
+        let mut stream = self.lock_owned().await.context("open_read", &[path])?;
+        let data_stream = stream
+            .retr_as_stream(path)
+            .await
+            .context("open_read", &[path])?;
+
+        Ok(Box::new(FTPReader {
+            transfer: Some((stream, data_stream.compat())),
+            finalize: None,
+        }))
+    }
+
+    async fn read_range(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut stream = self.lock().await.context("read_range", &[path])?;
+        stream
+            .resume_transfer(offset as usize)
+            .await
+            .context("read_range", &[path])?;
+
+        let mut data_stream = stream
+            .retr_as_stream(path)
+            .await
+            .context("read_range", &[path])?
+            .compat();
+        // One byte past the range tells whether the server is done sending, as it has already
+        // concluded the transfer when the range reaches EOF and must not be sent ABOR then
+        let mut contents = vec![];
+        (&mut data_stream)
+            .take(len.saturating_add(1))
+            .read_to_end(&mut contents)
+            .await
+            .context("read_range", &[path])?;
+
+        if contents.len() as u64 > len {
+            contents.truncate(len as usize);
+            stream
+                .abort(data_stream.into_inner())
+                .await
+                .context("read_range", &[path])?;
+        } else {
+            stream
+                .finalize_retr_stream(data_stream.into_inner())
+                .await
+                .context("read_range", &[path])?;
+        }
+
+        Ok(contents)
+    }
+}
+
+// The control connection is silent between commands, so anything readable on it means the server
+// has hung up: EOF, an error or an unsolicited reply like 421 sent before closing. The first poll
+// peeks at the socket directly, so it only stays pending if there is nothing to read.
+async fn is_closed(stream: &FTPBackendStream) -> bool {
+    let mut buf = [0; 1];
+    let mut peek = pin!(stream.get_ref().await.peek(&mut buf));
+
+    peek.as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+        .is_ready()
+}
+
+async fn keepalive(
+    stream: Weak<Mutex<FTPBackendStream>>,
+    connection_lost: Arc<AtomicBool>,
+    last_activity: Arc<std::sync::Mutex<Instant>>,
+    interval: Duration,
+) {
+    let mut wait = interval;
+    loop {
+        tokio::time::sleep(wait).await;
+        wait = interval;
+
+        let Some(stream) = stream.upgrade() else {
+            return;
+        };
+        // A busy connection needs no keepalive, it is idle from when the command holding it is done
+        let mut stream = match stream.try_lock() {
+            Ok(stream) => stream,
+            Err(_) => {
+                drop(stream.lock().await);
+                *last_activity.lock().unwrap() = Instant::now();
+                continue;
+            }
+        };
+
+        let idle = last_activity.lock().unwrap().elapsed();
+        if idle < interval {
+            wait = interval - idle;
+            continue;
+        }
+
+        if !connection_lost.load(Ordering::Acquire) && stream.noop().await.is_err() {
+            connection_lost.store(true, Ordering::Release);
+        }
+        *last_activity.lock().unwrap() = Instant::now();
+    }
+}
+
+// Returns `None` if nothing exists at `path`, which servers reply to with 550. Any other reply,
+// like a lost login or a busy server, says nothing about the file and is an error.
+async fn stat(stream: &mut FTPBackendStream, path: &str) -> Result<Option<Metadata>> {
+    match stream.mlst(Some(path)).await {
+        Ok(line) => return Ok(Some(metadata_from_mlst_facts(&parse_mlst_line(&line).0))),
+        Err(FtpError::UnexpectedResponse(response)) if is_unsupported_status(response.status) => {}
+        Err(FtpError::UnexpectedResponse(response))
+            if response.status == Status::FileUnavailable =>
+        {
+            return Ok(None)
+        }
+        Err(error) => return Err(error.into()),
+    }
+
+    // Fall back to SIZE/MDTM for files and CWD for directories on servers lacking MLST
+    match stream.size(path).await {
+        Ok(size) => {
+            return Ok(Some(Metadata {
+                r#type: FileType::File,
+                modified: stream
+                    .mdtm(path)
+                    .await
+                    .ok()
+                    .map(|modified| modified.and_utc().into()),
+                accessed: None,
+                created: None,
+                size: Some(size as u64),
+                readonly: false, // FIXME: Assumption
+                unix_mode: None,
+                link_target: None,
+                link_count: None,
+                inode: None,
+                device: None,
+                uid: None,
+                gid: None,
+                user: None,
+                group: None,
+            }));
+        }
+        // Directories are unavailable to SIZE as well
+        Err(FtpError::UnexpectedResponse(response))
+            if response.status == Status::FileUnavailable
+                || is_unsupported_status(response.status) => {}
+        Err(error) => return Err(error.into()),
+    }
+
+    let working_dir = stream.pwd().await?;
+    match stream.cwd(path).await {
+        Ok(()) => {
+            stream.cwd(working_dir).await?;
+            Ok(Some(Metadata {
+                r#type: FileType::Dir,
+                modified: None,
+                accessed: None,
+                created: None,
+                size: None,
+                readonly: false, // FIXME: Assumption
+                unix_mode: None,
+                link_target: None,
+                link_count: None,
+                inode: None,
+                device: None,
+                uid: None,
+                gid: None,
+                user: None,
+                group: None,
+            }))
+        }
+        Err(FtpError::UnexpectedResponse(response))
+            if response.status == Status::FileUnavailable =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+// 550 replies are shared by every failed file action, so the server is asked separately whether
+// nothing exists at `path` before reporting it as missing
+async fn check_missing<T>(
+    stream: &mut FTPBackendStream,
+    result: std::result::Result<T, FtpError>,
+    path: &str,
+) -> Result<T> {
+    if is_file_unavailable(&result) && matches!(stat(stream, path).await, Ok(None)) {
+        return Err(Error::FileNonexistent(path.to_string()));
+    }
+
+    Ok(result?)
+}
+
+fn is_file_unavailable<T>(result: &std::result::Result<T, FtpError>) -> bool {
+    matches!(
+        result,
+        Err(FtpError::UnexpectedResponse(response)) if response.status == Status::FileUnavailable
+    )
+}
+
+fn parent_dir(path: &str) -> &str {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+async fn supports_mlsd(stream: &mut FTPBackendStream) -> Result<bool> {
+    match stream.feat().await {
+        // Servers advertise MLST, which implies MLSD as well (RFC 3659)
+        Ok(features) => Ok(features.keys().any(|feature| {
+            feature.eq_ignore_ascii_case("MLST") || feature.eq_ignore_ascii_case("MLSD")
+        })),
+        Err(FtpError::UnexpectedResponse(_)) => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn file_in_dir(dir: &str, name: &str, metadata: Metadata) -> File {
+    let path = format!("{dir}/{name}");
+    let extension = Path::new(&path)
+        .extension()
+        .and_then(|os_str| os_str.to_str().map(|str| str.to_lowercase()));
+
+    File {
+        path,
+        name: name.to_string(),
+        extension,
+        metadata,
+    }
+}
+
+fn unix_mode_from_list_file(file: &ListFile) -> u32 {
+    [
+        (PosixPexQuery::Owner, 6),
+        (PosixPexQuery::Group, 3),
+        (PosixPexQuery::Others, 0),
+    ]
+    .into_iter()
+    .fold(0, |mode, (who, shift)| {
+        let bits = (file.can_read(who) as u32) << 2
+            | (file.can_write(who) as u32) << 1
+            | file.can_execute(who) as u32;
+        mode | bits << shift
+    })
+}
+
+struct ListEntry {
+    file: ListFile,
+    unix_mode: Option<u32>,
+    link_count: Option<u64>,
+}
+
+// Parses a LIST line as a Unix or DOS entry, the block count of `ls -l` and empty lines are
+// not entries and give None
+fn parse_list_line(line: &str) -> Result<Option<ListEntry>> {
+    if let Ok(file) = ListFile::from_posix_line(line) {
+        let unix_mode = unix_mode_from_list_file(&file);
+        // The link count follows the permissions, suppaftp does not keep it
+        let link_count = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|count| count.parse().ok());
+        return Ok(Some(ListEntry {
+            file,
+            unix_mode: Some(unix_mode),
+            link_count,
+        }));
+    }
+
+    if let Ok(file) = ListFile::from_dos_line(line) {
+        return Ok(Some(ListEntry {
+            file,
+            unix_mode: None,
+            link_count: None,
+        }));
+    }
+
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [] => Ok(None),
+        ["total", blocks] if blocks.parse::<u64>().is_ok() => Ok(None),
+        _ => Err(Error::UnparseableDirEntry(line.to_string())),
+    }
+}
+
+// suppaftp only keeps numeric owners, names are taken from the columns after the link count
+fn owner_names_from_list_line(line: &str) -> (Option<String>, Option<String>) {
+    let mut columns = line
+        .split_whitespace()
+        .skip(2)
+        .map(|column| Some(column.to_string()).filter(|name| name.parse::<u32>().is_err()));
+
+    (columns.next().flatten(), columns.next().flatten())
+}
+
+fn is_unsupported_status(status: Status) -> bool {
+    matches!(
+        status,
+        Status::BadCommand | Status::NotImplemented | Status::CommandNotImplemented
+    )
+}
+
+// Lines are formatted as `type=file;size=1024;modify=20240101000000; name` (RFC 3659),
+// fact names are case-insensitive so they are lowercased here.
+fn parse_mlst_line(line: &str) -> (HashMap<String, String>, &str) {
+    let (facts, name) = line.split_once(' ').unwrap_or((line, ""));
+
+    let facts = facts
+        .split(';')
+        .filter_map(|fact| fact.split_once('='))
+        .map(|(name, value)| (name.to_lowercase(), value.to_string()))
+        .collect();
+
+    (facts, name)
+}
+
+fn metadata_from_mlst_facts(facts: &HashMap<String, String>) -> Metadata {
+    let r#type = match facts.get("type").map(|value| value.to_lowercase()) {
+        Some(value) => match value.as_str() {
+            "file" => FileType::File,
+            "dir" | "cdir" | "pdir" => FileType::Dir,
+            _ if value.starts_with("os.unix=slink") || value.starts_with("os.unix=symlink") => {
+                FileType::Symlink
+            }
+            _ => FileType::Unknown,
+        },
+        None => FileType::Unknown,
+    };
+    let (uid, user) = owner_from_mlst_facts(facts, "unix.uid", "unix.owner", "unix.ownername");
+    let (gid, group) = owner_from_mlst_facts(facts, "unix.gid", "unix.group", "unix.groupname");
+
+    Metadata {
+        r#type,
+        modified: facts.get("modify").and_then(|value| parse_mlst_time(value)),
+        accessed: None,
+        created: facts.get("create").and_then(|value| parse_mlst_time(value)),
+        size: facts
+            .get("size")
+            .or_else(|| facts.get("sizd"))
+            .and_then(|value| value.parse().ok()),
+        // Files need write or append permission and directories need create or mkdir permission
+        readonly: facts
+            .get("perm")
+            .is_some_and(|perm| !perm.to_lowercase().contains(['w', 'a', 'c', 'm'])),
+        unix_mode: facts
+            .get("unix.mode")
+            .and_then(|value| u32::from_str_radix(value, 8).ok())
+            .map(|mode| mode.into()),
+        // Some servers append the target to the type, as in `OS.unix=slink:/target`
+        link_target: facts
+            .get("type")
+            .and_then(|value| value.split_once(':'))
+            .filter(|(r#type, _)| r#type.eq_ignore_ascii_case("os.unix=slink"))
+            .map(|(_, target)| target.to_string()),
+        link_count: None,
+        inode: None,
+        device: None,
+        uid,
+        gid,
+        user,
+        group,
+    }
+}
+
+// Servers disagree on whether `UNIX.owner`/`UNIX.group` hold ids or names, some send both
+fn owner_from_mlst_facts(
+    facts: &HashMap<String, String>,
+    id_fact: &str,
+    owner_fact: &str,
+    name_fact: &str,
+) -> (Option<u32>, Option<String>) {
+    let owner = facts.get(owner_fact);
+
+    let id = facts
+        .get(id_fact)
+        .or(owner)
+        .and_then(|value| value.parse().ok());
+    let name = facts
+        .get(name_fact)
+        .or(owner.filter(|value| value.parse::<u32>().is_err()))
+        .cloned();
+    (id, name)
+}
+
+// Times are always UTC, in the form `YYYYMMDDHHMMSS[.sss]`
+fn parse_mlst_time(value: &str) -> Option<SystemTime> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S%.f")
+        .ok()
+        .map(|time| time.and_utc().into())
+}
+
+type FTPFinalizeFuture = Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+// Holds the control connection for as long as the data connection is open,
+// the transfer is finalized once the data stream reaches EOF.
+struct FTPReader<D: futures_io::AsyncRead + Unpin + Send + 'static> {
+    transfer: Option<(OwnedMutexGuard<FTPBackendStream>, Compat<D>)>,
+    finalize: Option<FTPFinalizeFuture>,
+}
+
+impl<D: futures_io::AsyncRead + Unpin + Send + 'static> AsyncRead for FTPReader<D> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Some((_, data_stream)) = self.transfer.as_mut() {
+            let filled = buf.filled().len();
+            ready!(Pin::new(data_stream).poll_read(cx, buf))?;
+
+            if buf.filled().len() > filled || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            let (mut stream, data_stream) = self.transfer.take().unwrap();
+            self.finalize = Some(Box::pin(async move {
+                stream
+                    .finalize_retr_stream(data_stream.into_inner())
+                    .await
+                    .map_err(io::Error::other)
+            }));
+        }
+
+        if let Some(finalize) = self.finalize.as_mut() {
+            ready!(finalize.as_mut().poll(cx))?;
+            self.finalize = None;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<D: futures_io::AsyncRead + Unpin + Send + 'static> Drop for FTPReader<D> {
+    fn drop(&mut self) {
+        // The server still expects the transfer to be concluded if the reader is dropped early
+        if let (Some((mut stream, data_stream)), Ok(handle)) =
+            (self.transfer.take(), Handle::try_current())
+        {
+            handle.spawn(async move {
+                let _ = stream.abort(data_stream.into_inner()).await;
+            });
+        }
+    }
+}
+
+// Like `FTPReader`, but the transfer is only finalized when the writer is shut down.
+struct FTPWriter<D: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin + Send + 'static> {
+    path: String,
+    transfer: Option<(OwnedMutexGuard<FTPBackendStream>, Compat<D>)>,
+    finalize: Option<FTPFinalizeFuture>,
+}
+
+impl<D: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin + Send + 'static> AsyncWrite
+    for FTPWriter<D>
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.transfer.as_mut() {
+            Some((_, data_stream)) => Pin::new(data_stream).poll_write(cx, buf),
+            None => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.transfer.as_mut() {
+            Some((_, data_stream)) => Pin::new(data_stream).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some((mut stream, data_stream)) = self.transfer.take() {
+            self.finalize = Some(Box::pin(async move {
+                stream
+                    .finalize_put_stream(data_stream.into_inner())
+                    .await
+                    .map_err(io::Error::other)
+            }));
+        }
+
+        if let Some(finalize) = self.finalize.as_mut() {
+            ready!(finalize.as_mut().poll(cx))?;
+            self.finalize = None;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<D: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin + Send + 'static> Drop
+    for FTPWriter<D>
+{
+    fn drop(&mut self) {
+        // A writer that was never shut down is abandoned. Servers cannot tell a closed data
+        // connection from a finished upload, so the partial file is removed after aborting, any
+        // previous contents were already replaced once the upload started.
+        if let (Some((mut stream, data_stream)), Ok(handle)) =
+            (self.transfer.take(), Handle::try_current())
+        {
+            let path = std::mem::take(&mut self.path);
+            handle.spawn(async move {
+                let _ = stream.abort(data_stream.into_inner()).await;
+                let _ = stream.rm(&path).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mlst_facts() {
+        let (facts, name) = parse_mlst_line(
+            "Type=file;Size=1024;Modify=20240102030405.5;Perm=r;UNIX.mode=0644; /dir/file.txt",
+        );
+        let metadata = metadata_from_mlst_facts(&facts);
+
+        assert_eq!(name, "/dir/file.txt");
+        assert_eq!(metadata.r#type, FileType::File);
+        assert_eq!(metadata.size, Some(1024));
+        assert_eq!(
+            metadata.modified,
+            Some(
+                NaiveDateTime::parse_from_str("2024-01-02 03:04:05.5", "%Y-%m-%d %H:%M:%S%.f")
+                    .unwrap()
+                    .and_utc()
+                    .into()
+            )
+        );
+        assert!(metadata.readonly);
+        assert_eq!(metadata.unix_mode.map(|mode| mode.mode()), Some(0o644));
+
+        let (facts, _) = parse_mlst_line("type=OS.unix=slink:/target;modify=20240102030405; link");
+        let metadata = metadata_from_mlst_facts(&facts);
+
+        assert_eq!(metadata.r#type, FileType::Symlink);
+        assert_eq!(metadata.link_target.as_deref(), Some("/target"));
+        assert!(metadata.modified.is_some());
+        assert!(!metadata.readonly);
+    }
+    #[test]
+    fn list_unix_mode() {
+        let file = ListFile::from_posix_line("-rwxr-x--- 1 0 1 8192 Nov 5 2018 script.sh").unwrap();
+
+        assert_eq!(unix_mode_from_list_file(&file), 0o750);
+    }
+
+    #[test]
+    fn list_non_entries() {
+        assert!(parse_list_line("total 16").unwrap().is_none());
+        assert!(parse_list_line("").unwrap().is_none());
+        assert!(matches!(
+            parse_list_line("not a listing line"),
+            Err(Error::UnparseableDirEntry(line)) if line == "not a listing line"
+        ));
+    }
 }
@@ -0,0 +1,636 @@
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, ErrorKind};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWrite;
+
+use crate::data::{Capabilities, File, FileType, Metadata};
+use crate::error::{Error, Result, ResultExt};
+use crate::{FSBackend, FileReader, FileWriter};
+
+// Symlinks are followed at most this many times before giving up, like Linux's ELOOP limit
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+type Nodes = BTreeMap<String, Node>;
+
+// Paths are normalized to absolute, `/`-separated paths, the root directory always exists.
+// Nodes are stored under their resolved path, which never goes through a symlink.
+#[derive(Clone)]
+pub struct MemoryBackend {
+    nodes: Arc<Mutex<Nodes>>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    kind: NodeKind,
+    modified: SystemTime,
+    accessed: SystemTime,
+    created: SystemTime,
+    permissions: u32,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    File(Vec<u8>),
+    Dir,
+    Symlink(String),
+}
+
+impl Node {
+    fn new(kind: NodeKind) -> Self {
+        let now = SystemTime::now();
+        let permissions = match kind {
+            NodeKind::File(_) => 0o644,
+            NodeKind::Dir => 0o755,
+            NodeKind::Symlink(_) => 0o777,
+        };
+
+        Self {
+            kind,
+            modified: now,
+            accessed: now,
+            created: now,
+            permissions,
+        }
+    }
+
+    fn file_type(&self) -> FileType {
+        match self.kind {
+            NodeKind::File(_) => FileType::File,
+            NodeKind::Dir => FileType::Dir,
+            NodeKind::Symlink(_) => FileType::Symlink,
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        let (size, type_bits) = match &self.kind {
+            NodeKind::File(contents) => (contents.len() as u64, 0o100000),
+            NodeKind::Dir => (0, 0o040000),
+            NodeKind::Symlink(target) => (target.len() as u64, 0o120000),
+        };
+
+        Metadata {
+            r#type: self.file_type(),
+            modified: Some(self.modified),
+            accessed: Some(self.accessed),
+            created: Some(self.created),
+            size: Some(size),
+            readonly: self.permissions & 0o222 == 0,
+            unix_mode: Some((type_bits | self.permissions).into()),
+            link_target: match &self.kind {
+                NodeKind::Symlink(target) => Some(target.clone()),
+                _ => None,
+            },
+            link_count: None,
+            inode: None,
+            device: None,
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+        }
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        let mut nodes = Nodes::new();
+        nodes.insert("/".into(), Node::new(NodeKind::Dir));
+
+        Self {
+            nodes: Arc::new(Mutex::new(nodes)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Nodes> {
+        self.nodes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FSBackend for MemoryBackend {
+    fn capabilities(&self) -> Capabilities {
+        // Nodes own their contents, so there is nothing for a second name to share, there are no
+        // users to own them and nowhere to recover trashed files from
+        Capabilities::SET_PERMISSIONS
+            | Capabilities::SYMLINKS
+            | Capabilities::SERVER_SIDE_COPY
+            | Capabilities::ATOMIC_RENAME
+            | Capabilities::RANGED_READS
+            | Capabilities::APPEND
+            | Capabilities::MODIFIED_TIME
+            | Capabilities::ACCESSED_TIME
+            | Capabilities::CREATED_TIME
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        // NOOP
+        Ok(())
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        Ok(resolve_symlinks(&self.lock(), path).is_ok())
+    }
+
+    async fn get_file_type(&self, path: &str) -> Result<FileType> {
+        let nodes = self.lock();
+        let path = resolve_symlinks(&nodes, path).context("get_file_type", &[path])?;
+
+        Ok(nodes[&path].file_type())
+    }
+
+    async fn retrieve_files(&self, paths: &[&str], follow_symlinks: bool) -> Result<Vec<File>> {
+        let nodes = self.lock();
+        let mut files = vec![];
+
+        for path in paths {
+            let resolved_path = if follow_symlinks {
+                resolve_symlinks(&nodes, path)
+            } else {
+                existing_path(&nodes, path)
+            }
+            .context("retrieve_files", &[path])?;
+            let path_std = Path::new(path);
+
+            files.push(File {
+                path: path.to_string(),
+                name: path_std
+                    .file_name()
+                    .ok_or(Error::NoFileName)
+                    .context("retrieve_files", &[path])?
+                    .to_str()
+                    .unwrap() // Input paths are already Unicode
+                    .to_string(),
+                extension: path_std
+                    .extension()
+                    .map(|os_str| os_str.to_str().unwrap().to_lowercase()), // Input paths are already Unicode
+                metadata: nodes[&resolved_path].metadata(),
+            });
+        }
+
+        Ok(files)
+    }
+
+    async fn symlink_metadata(&self, path: &str) -> Result<Metadata> {
+        let nodes = self.lock();
+        let path = existing_path(&nodes, path).context("symlink_metadata", &[path])?;
+
+        Ok(nodes[&path].metadata())
+    }
+
+    async fn retrieve_file_content(&self, path: &str) -> Result<Vec<u8>> {
+        let mut nodes = self.lock();
+        let resolved_path =
+            resolve_symlinks(&nodes, path).context("retrieve_file_content", &[path])?;
+        let node = nodes.get_mut(&resolved_path).unwrap();
+
+        match &node.kind {
+            NodeKind::File(contents) => {
+                let contents = contents.clone();
+                node.accessed = SystemTime::now();
+                Ok(contents)
+            }
+            _ => Err(ErrorKind::IsADirectory).context("retrieve_file_content", &[path]),
+        }
+    }
+
+    async fn read_dir(&self, path: &str) -> Result<Vec<File>> {
+        let nodes = self.lock();
+        let dir = resolve_symlinks(&nodes, path).context("read_dir", &[path])?;
+
+        if !matches!(nodes[&dir].kind, NodeKind::Dir) {
+            return Err(ErrorKind::NotADirectory).context("read_dir", &[path]);
+        }
+
+        // Entries are reported under the path that was asked for, not the resolved one
+        let listed_prefix = child_prefix(&normalize_path(path));
+
+        Ok(children(&nodes, &dir)
+            .map(|(child_path, node)| {
+                let name = child_path[child_prefix(&dir).len()..].to_string();
+                let extension = Path::new(&name)
+                    .extension()
+                    .and_then(|os_str| os_str.to_str().map(|str| str.to_lowercase()));
+
+                File {
+                    path: format!("{listed_prefix}{name}"),
+                    name,
+                    extension,
+                    metadata: node.metadata(),
+                }
+            })
+            .collect())
+    }
+
+    async fn create_file(
+        &self,
+        path: &str,
+        overwrite: bool,
+        contents: Option<&[u8]>,
+    ) -> Result<()> {
+        let mut nodes = self.lock();
+        let path = writable_file_path(&nodes, path, overwrite).context("create_file", &[path])?;
+
+        nodes.insert(
+            path,
+            Node::new(NodeKind::File(contents.unwrap_or(&[]).to_vec())),
+        );
+        Ok(())
+    }
+
+    async fn append_file(&self, path: &str, contents: &[u8]) -> Result<()> {
+        let mut nodes = self.lock();
+        let resolved_path = match resolve_symlinks(&nodes, path) {
+            Ok(resolved_path) => resolved_path,
+            Err(_) => writable_file_path(&nodes, path, false).context("append_file", &[path])?,
+        };
+
+        let node = nodes
+            .entry(resolved_path)
+            .or_insert_with(|| Node::new(NodeKind::File(vec![])));
+
+        match &mut node.kind {
+            NodeKind::File(existing_contents) => {
+                existing_contents.extend_from_slice(contents);
+                node.modified = SystemTime::now();
+                Ok(())
+            }
+            _ => Err(ErrorKind::IsADirectory).context("append_file", &[path]),
+        }
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<()> {
+        let mut nodes = self.lock();
+        let path = resolve_path(&nodes, path, false).context("create_dir", &[path])?;
+
+        if nodes.contains_key(&path) {
+            return Err(Error::FileAlreadyExists(path.clone()).context("create_dir", &[&path]));
+        }
+        check_parent_dir(&nodes, &path).context("create_dir", &[&path])?;
+
+        nodes.insert(path, Node::new(NodeKind::Dir));
+        Ok(())
+    }
+
+    async fn rename_file(&self, path: &str, new_name: &str, overwrite: bool) -> Result<()> {
+        let new_path = format!(
+            "{}{new_name}",
+            child_prefix(&parent_path(&normalize_path(path)))
+        );
+        self.move_file(path, &new_path, overwrite).await
+    }
+
+    async fn move_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        move_node(&mut self.lock(), from, to, overwrite).context("move_file", &[from, to])
+    }
+
+    async fn copy_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        let mut nodes = self.lock();
+        let resolved_from = resolve_symlinks(&nodes, from).context("copy_file", &[from, to])?;
+
+        let NodeKind::File(contents) = &nodes[&resolved_from].kind else {
+            return Err(ErrorKind::InvalidInput).context("copy_file", &[from, to]);
+        };
+        let mut node = Node::new(NodeKind::File(contents.clone()));
+        node.permissions = nodes[&resolved_from].permissions;
+
+        let resolved_to =
+            writable_file_path(&nodes, to, overwrite).context("copy_file", &[from, to])?;
+        nodes.insert(resolved_to, node);
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        let mut nodes = self.lock();
+        let path = resolve_path(&nodes, path, false).context("remove_file", &[path])?;
+
+        match nodes.get(&path).map(|node| &node.kind) {
+            Some(NodeKind::Dir) => Err(ErrorKind::IsADirectory).context("remove_file", &[&path]),
+            Some(_) => {
+                nodes.remove(&path);
+                Ok(())
+            }
+            None => Err(Error::FileNonexistent(path.clone())).context("remove_file", &[&path]),
+        }
+    }
+
+    async fn remove_dir(&self, path: &str) -> Result<()> {
+        let mut nodes = self.lock();
+        let path = resolve_path(&nodes, path, false).context("remove_dir", &[path])?;
+
+        match nodes.get(&path).map(|node| &node.kind) {
+            Some(NodeKind::Dir) => {
+                if children(&nodes, &path).next().is_some() {
+                    return Err(ErrorKind::DirectoryNotEmpty).context("remove_dir", &[&path]);
+                }
+
+                nodes.remove(&path);
+                Ok(())
+            }
+            Some(_) => Err(ErrorKind::NotADirectory).context("remove_dir", &[&path]),
+            None => Err(Error::FileNonexistent(path.clone())).context("remove_dir", &[&path]),
+        }
+    }
+
+    async fn trash(&self, _paths: &[&str]) -> Result<()> {
+        Err(Error::Unsupported("trash".into(), "Memory".into()))
+    }
+
+    async fn set_file_permissions_unix(&self, path: &str, mode: u32) -> Result<()> {
+        let mut nodes = self.lock();
+        let path = resolve_symlinks(&nodes, path).context("set_file_permissions_unix", &[path])?;
+
+        nodes.get_mut(&path).unwrap().permissions = mode & 0o7777;
+        Ok(())
+    }
+
+    async fn read_link(&self, path: &str) -> Result<String> {
+        let nodes = self.lock();
+        let path = resolve_path(&nodes, path, false).context("read_link", &[path])?;
+
+        match nodes.get(&path).map(|node| &node.kind) {
+            Some(NodeKind::Symlink(target)) => Ok(target.clone()),
+            // Like readlink(2) on anything but a symlink
+            Some(_) => Err(ErrorKind::InvalidInput).context("read_link", &[&path]),
+            None => Err(Error::FileNonexistent(path.clone())).context("read_link", &[&path]),
+        }
+    }
+
+    async fn create_symlink(&self, path: &str, target: &str) -> Result<()> {
+        let mut nodes = self.lock();
+        let path = resolve_path(&nodes, path, false).context("create_symlink", &[path])?;
+
+        if nodes.contains_key(&path) {
+            return Err(Error::FileAlreadyExists(path.clone()).context("create_symlink", &[&path]));
+        }
+        check_parent_dir(&nodes, &path).context("create_symlink", &[&path])?;
+
+        nodes.insert(path, Node::new(NodeKind::Symlink(target.into())));
+        Ok(())
+    }
+
+    async fn open_write(&self, path: &str, overwrite: bool) -> Result<FileWriter> {
+        self.create_file(path, overwrite, None).await?;
+
+        let path = resolve_symlinks(&self.lock(), path).context("open_write", &[path])?;
+        Ok(Box::new(MemoryWriter {
+            nodes: self.nodes.clone(),
+            path,
+        }))
+    }
+
+    async fn open_read(&self, path: &str) -> Result<FileReader> {
+        Ok(Box::new(Cursor::new(
+            self.retrieve_file_content(path).await?,
+        )))
+    }
+}
+
+// Writes straight into the file's node, so they are visible before the writer is shut down
+struct MemoryWriter {
+    nodes: Arc<Mutex<Nodes>>,
+    path: String,
+}
+
+impl AsyncWrite for MemoryWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut nodes = self
+            .nodes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match nodes.get_mut(&self.path) {
+            Some(Node {
+                kind: NodeKind::File(contents),
+                modified,
+                ..
+            }) => {
+                contents.extend_from_slice(buf);
+                *modified = SystemTime::now();
+                Poll::Ready(Ok(buf.len()))
+            }
+            _ => Poll::Ready(Err(ErrorKind::NotFound.into())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let mut components = vec![];
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+
+    format!("/{}", components.join("/"))
+}
+
+fn parent_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".into(),
+        Some(index) => path[..index].into(),
+    }
+}
+
+fn child_prefix(dir: &str) -> String {
+    if dir == "/" {
+        dir.into()
+    } else {
+        format!("{dir}/")
+    }
+}
+
+fn children<'a>(nodes: &'a Nodes, dir: &str) -> impl Iterator<Item = (&'a String, &'a Node)> {
+    let prefix = child_prefix(dir);
+    let prefix_len = prefix.len();
+
+    nodes
+        .range(prefix.clone()..)
+        .take_while(move |(path, _)| path.starts_with(&prefix))
+        .filter(move |(path, _)| path.len() > prefix_len && !path[prefix_len..].contains('/'))
+}
+
+// Follows symlinks in every component of `path`, the last one only if `follow_last` is set, and
+// returns the path the node is stored under. The last component does not have to exist.
+fn resolve_path(nodes: &Nodes, path: &str, follow_last: bool) -> Result<String> {
+    let mut pending = path_components(path);
+    let mut resolved = String::from("/");
+    let mut followed = 0;
+
+    while let Some(component) = pending.pop() {
+        // After a symlink, `..` leads to the parent of its target like it does in the kernel
+        if component == ".." {
+            resolved = parent_path(&resolved);
+            continue;
+        }
+
+        let candidate = format!("{}{component}", child_prefix(&resolved));
+        let is_last = pending.is_empty();
+
+        match nodes.get(&candidate).map(|node| &node.kind) {
+            Some(NodeKind::Symlink(target)) if !is_last || follow_last => {
+                followed += 1;
+                if followed > MAX_SYMLINK_DEPTH {
+                    return Err(Error::SymlinkLoop(path.to_string()));
+                }
+
+                // Relative targets start from the directory containing the symlink
+                if target.starts_with('/') {
+                    resolved = "/".into();
+                }
+                pending.extend(path_components(target));
+            }
+            Some(NodeKind::File(_) | NodeKind::Symlink(_)) if !is_last => {
+                return Err(ErrorKind::NotADirectory.into())
+            }
+            Some(_) => resolved = candidate,
+            None if is_last => resolved = candidate,
+            None => return Err(Error::FileNonexistent(path.to_string())),
+        }
+    }
+
+    Ok(resolved)
+}
+
+// In reverse, so the next component can be popped off
+fn path_components(path: &str) -> Vec<String> {
+    path.split('/')
+        .rev()
+        .filter(|component| !component.is_empty() && *component != ".")
+        .map(String::from)
+        .collect()
+}
+
+fn resolve_symlinks(nodes: &Nodes, path: &str) -> Result<String> {
+    let resolved = resolve_path(nodes, path, true)?;
+
+    if nodes.contains_key(&resolved) {
+        Ok(resolved)
+    } else {
+        Err(Error::FileNonexistent(path.to_string()))
+    }
+}
+
+// Like `resolve_symlinks`, but a symlink at `path` itself is not followed
+fn existing_path(nodes: &Nodes, path: &str) -> Result<String> {
+    let resolved = resolve_path(nodes, path, false)?;
+
+    if nodes.contains_key(&resolved) {
+        Ok(resolved)
+    } else {
+        Err(Error::FileNonexistent(path.to_string()))
+    }
+}
+
+fn check_parent_dir(nodes: &Nodes, path: &str) -> Result<()> {
+    let parent = parent_path(path);
+
+    match resolve_symlinks(nodes, &parent)
+        .ok()
+        .and_then(|parent| nodes.get(&parent))
+    {
+        Some(Node {
+            kind: NodeKind::Dir,
+            ..
+        }) => Ok(()),
+        Some(_) => Err(ErrorKind::NotADirectory.into()),
+        None => Err(Error::FileNonexistent(parent)),
+    }
+}
+
+// Moves the node at `from` along with everything below it to `to`
+fn move_node(nodes: &mut Nodes, from: &str, to: &str, overwrite: bool) -> Result<()> {
+    let from = resolve_path(nodes, from, false)?;
+    let to = resolve_path(nodes, to, false)?;
+
+    if !nodes.contains_key(&from) {
+        return Err(Error::FileNonexistent(from));
+    }
+    if from == to {
+        return Ok(());
+    }
+    if to.starts_with(&child_prefix(&from)) {
+        return Err(ErrorKind::InvalidInput.into());
+    }
+
+    if let Some(existing) = nodes.get(&to) {
+        if !overwrite {
+            return Err(Error::FileAlreadyExists(to));
+        }
+
+        match (&nodes[&from].kind, &existing.kind) {
+            (NodeKind::Dir, NodeKind::Dir) if children(nodes, &to).next().is_some() => {
+                return Err(ErrorKind::DirectoryNotEmpty.into())
+            }
+            (NodeKind::Dir, NodeKind::Dir) => {}
+            (NodeKind::Dir, _) => return Err(ErrorKind::NotADirectory.into()),
+            (_, NodeKind::Dir) => return Err(ErrorKind::IsADirectory.into()),
+            _ => {}
+        }
+    } else {
+        check_parent_dir(nodes, &to)?;
+    }
+
+    let from_prefix = child_prefix(&from);
+    let moved_paths: Vec<String> = std::iter::once(from.clone())
+        .chain(
+            nodes
+                .range(from_prefix.clone()..)
+                .map(|(path, _)| path)
+                .take_while(|path| path.starts_with(&from_prefix))
+                .cloned(),
+        )
+        .collect();
+
+    for moved_path in moved_paths {
+        let node = nodes.remove(&moved_path).unwrap();
+        nodes.insert(format!("{to}{}", &moved_path[from.len()..]), node);
+    }
+
+    Ok(())
+}
+
+// Resolves the path a file may be (re)created at, following a symlink at `path` like `open` does
+fn writable_file_path(nodes: &Nodes, path: &str, overwrite: bool) -> Result<String> {
+    let resolved = resolve_path(nodes, path, true)?;
+
+    match nodes.get(&resolved).map(|node| &node.kind) {
+        Some(_) if !overwrite => Err(Error::FileAlreadyExists(normalize_path(path))),
+        Some(NodeKind::Dir) => Err(ErrorKind::IsADirectory.into()),
+        Some(_) => Ok(resolved),
+        None => {
+            check_parent_dir(nodes, &resolved)?;
+            Ok(resolved)
+        }
+    }
+}
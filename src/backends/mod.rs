@@ -1,3 +1,7 @@
 pub mod ftp;
+pub mod memory;
+pub mod pooled_ftp;
+pub mod reconnect;
+pub mod retry;
 pub mod sftp;
 pub mod std;
@@ -0,0 +1,437 @@
+use std::io;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::backends::ftp::{FTPBackend, FTPBuilder};
+use crate::data::{Capabilities, File, FileType, Metadata};
+use crate::error::{network_io_error_kind, Error, ErrorKind, Result, ResultExt};
+use crate::{FSBackend, FileReader, FileWriter};
+
+// Idle connections older than this are probed with NOOP before being handed out
+const IDLE_CHECK_AFTER: Duration = Duration::from_secs(15);
+
+// Spreads operations over several FTP control connections so they can run concurrently
+pub struct PooledFTPBackend {
+    pool: Arc<Pool>,
+}
+
+struct Pool {
+    builder: FTPBuilder,
+    size: usize,
+    idle: Mutex<Vec<(FTPBackend, Instant)>>,
+    permits: Arc<Semaphore>,
+    closed: AtomicBool,
+}
+
+impl PooledFTPBackend {
+    pub async fn new(builder: FTPBuilder, size: usize) -> Result<Self> {
+        if size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "An FTP pool needs at least one connection",
+            )
+            .into());
+        }
+
+        let mut idle = vec![];
+        for _ in 0..size {
+            idle.push((builder.clone().build().await?, Instant::now()));
+        }
+
+        Ok(Self {
+            pool: Arc::new(Pool {
+                builder,
+                size,
+                idle: Mutex::new(idle),
+                permits: Arc::new(Semaphore::new(size)),
+                closed: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.pool.size
+    }
+
+    async fn checkout(&self) -> Result<PooledConnection> {
+        self.check_open()?;
+        let permit = self
+            .pool
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("FTP pool semaphore is never closed");
+
+        self.connection(permit).await
+    }
+
+    fn check_open(&self) -> Result<()> {
+        if self.pool.closed.load(Ordering::Acquire) {
+            return Err(io::Error::other("The FTP pool has been disconnected").into());
+        }
+
+        Ok(())
+    }
+
+    async fn connection(&self, permit: OwnedSemaphorePermit) -> Result<PooledConnection> {
+        self.check_open()?;
+
+        loop {
+            let idle = self.pool.idle.lock().unwrap().pop();
+
+            let backend = match idle {
+                Some((backend, idle_since)) if idle_since.elapsed() >= IDLE_CHECK_AFTER => {
+                    if backend.stream.lock().await.noop().await.is_err() {
+                        continue;
+                    }
+                    backend
+                }
+                Some((backend, _)) => backend,
+                None => self.pool.builder.clone().build().await?,
+            };
+
+            return Ok(PooledConnection {
+                backend: Some(backend),
+                pool: self.pool.clone(),
+                broken: false,
+                _permit: permit,
+            });
+        }
+    }
+}
+
+impl FTPBuilder {
+    pub async fn build_pool(self, size: usize) -> Result<PooledFTPBackend> {
+        PooledFTPBackend::new(self, size).await
+    }
+}
+
+// Returns the connection to the pool when dropped, unless it failed at the connection level
+struct PooledConnection {
+    backend: Option<FTPBackend>,
+    pool: Arc<Pool>,
+    broken: bool,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledConnection {
+    fn release<T>(mut self, result: Result<T>) -> Result<T> {
+        if let Err(error) = &result {
+            self.broken = is_connection_error(error);
+        }
+
+        result
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = FTPBackend;
+
+    fn deref(&self) -> &FTPBackend {
+        self.backend.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if self.broken || self.pool.closed.load(Ordering::Acquire) {
+            return;
+        }
+
+        if let Some(backend) = self.backend.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap()
+                .push((backend, Instant::now()));
+        }
+    }
+}
+
+fn is_connection_error(error: &Error) -> bool {
+    matches!(error.kind(), ErrorKind::ConnectionLost | ErrorKind::Timeout)
+}
+
+macro_rules! with_connection {
+    ($self:ident, $name:literal, $paths:expr, $connection:ident => $operation:expr) => {{
+        let $connection = $self.checkout().await.context($name, $paths)?;
+        let result = $operation.await;
+        $connection.release(result)
+    }};
+}
+
+#[async_trait]
+impl FSBackend for PooledFTPBackend {
+    fn capabilities(&self) -> Capabilities {
+        FTPBackend::CAPABILITIES
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        self.pool.closed.store(true, Ordering::Release);
+
+        let idle = std::mem::take(&mut *self.pool.idle.lock().unwrap());
+        for (backend, _) in idle {
+            backend.disconnect().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        with_connection!(self, "exists", &[path], connection => connection.exists(path))
+    }
+
+    async fn get_file_type(&self, path: &str) -> Result<FileType> {
+        with_connection!(
+            self,
+            "get_file_type",
+            &[path],
+            connection => connection.get_file_type(path)
+        )
+    }
+
+    async fn retrieve_files(&self, paths: &[&str], follow_symlinks: bool) -> Result<Vec<File>> {
+        with_connection!(
+            self,
+            "retrieve_files",
+            paths,
+            connection => connection.retrieve_files(paths, follow_symlinks)
+        )
+    }
+
+    async fn symlink_metadata(&self, path: &str) -> Result<Metadata> {
+        with_connection!(
+            self,
+            "symlink_metadata",
+            &[path],
+            connection => connection.symlink_metadata(path)
+        )
+    }
+
+    async fn retrieve_file_content(&self, path: &str) -> Result<Vec<u8>> {
+        with_connection!(
+            self,
+            "retrieve_file_content",
+            &[path],
+            connection => connection.retrieve_file_content(path)
+        )
+    }
+
+    async fn read_dir(&self, path: &str) -> Result<Vec<File>> {
+        with_connection!(self, "read_dir", &[path], connection => connection.read_dir(path))
+    }
+
+    async fn create_file(
+        &self,
+        path: &str,
+        overwrite: bool,
+        contents: Option<&[u8]>,
+    ) -> Result<()> {
+        with_connection!(
+            self,
+            "create_file",
+            &[path],
+            connection => connection.create_file(path, overwrite, contents)
+        )
+    }
+
+    async fn append_file(&self, path: &str, contents: &[u8]) -> Result<()> {
+        with_connection!(
+            self,
+            "append_file",
+            &[path],
+            connection => connection.append_file(path, contents)
+        )
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<()> {
+        with_connection!(self, "create_dir", &[path], connection => connection.create_dir(path))
+    }
+
+    async fn rename_file(&self, path: &str, new_name: &str, overwrite: bool) -> Result<()> {
+        with_connection!(
+            self,
+            "rename_file",
+            &[path, new_name],
+            connection => connection.rename_file(path, new_name, overwrite)
+        )
+    }
+
+    async fn move_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        with_connection!(
+            self,
+            "move_file",
+            &[from, to],
+            connection => connection.move_file(from, to, overwrite)
+        )
+    }
+
+    async fn copy_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        // Streaming needs one connection for each side of the copy
+        if self.pool.size < 2 {
+            return with_connection!(
+                self,
+                "copy_file",
+                &[from, to],
+                connection => connection.copy_file(from, to, overwrite)
+            );
+        }
+
+        // Both are taken at once, copies holding one connection each while waiting for a second
+        // one would otherwise wait on each other forever
+        self.check_open().context("copy_file", &[from, to])?;
+        let mut permits = self
+            .pool
+            .permits
+            .clone()
+            .acquire_many_owned(2)
+            .await
+            .expect("FTP pool semaphore is never closed");
+        let read_permit = permits.split(1).unwrap();
+
+        let reader_connection = self
+            .connection(read_permit)
+            .await
+            .context("copy_file", &[from, to])?;
+        let writer_connection = self
+            .connection(permits)
+            .await
+            .context("copy_file", &[from, to])?;
+        let mut reader = open_read(reader_connection, from).await?;
+        let mut writer = open_write(writer_connection, to, overwrite).await?;
+
+        tokio::io::copy(&mut reader, &mut writer)
+            .await
+            .context("copy_file", &[from, to])?;
+        writer.shutdown().await.context("copy_file", &[from, to])?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        with_connection!(self, "remove_file", &[path], connection => connection.remove_file(path))
+    }
+
+    async fn remove_dir(&self, path: &str) -> Result<()> {
+        with_connection!(self, "remove_dir", &[path], connection => connection.remove_dir(path))
+    }
+
+    async fn trash(&self, _paths: &[&str]) -> Result<()> {
+        Err(Error::Unsupported("trash".into(), "FTP".into()))
+    }
+
+    async fn set_file_permissions_unix(&self, path: &str, mode: u32) -> Result<()> {
+        with_connection!(
+            self,
+            "set_file_permissions_unix",
+            &[path],
+            connection => connection.set_file_permissions_unix(path, mode)
+        )
+    }
+
+    async fn open_write(&self, path: &str, overwrite: bool) -> Result<FileWriter> {
+        open_write(
+            self.checkout().await.context("open_write", &[path])?,
+            path,
+            overwrite,
+        )
+        .await
+    }
+
+    async fn open_read(&self, path: &str) -> Result<FileReader> {
+        open_read(self.checkout().await.context("open_read", &[path])?, path).await
+    }
+
+    async fn read_range(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        with_connection!(
+            self,
+            "read_range",
+            &[path],
+            connection => connection.read_range(path, offset, len)
+        )
+    }
+}
+
+async fn open_write(
+    connection: PooledConnection,
+    path: &str,
+    overwrite: bool,
+) -> Result<FileWriter<'static>> {
+    match connection.open_owned_write(path, overwrite).await {
+        Ok(writer) => Ok(Box::new(PooledTransfer {
+            transfer: writer,
+            connection,
+        })),
+        Err(error) => connection.release(Err(error)),
+    }
+}
+
+async fn open_read(connection: PooledConnection, path: &str) -> Result<FileReader> {
+    match connection.open_read(path).await {
+        Ok(reader) => Ok(Box::new(PooledTransfer {
+            transfer: reader,
+            connection,
+        })),
+        Err(error) => connection.release(Err(error)),
+    }
+}
+
+// Keeps the connection checked out until the transfer is dropped, the transfer is declared
+// first so it is dropped, and thereby finalized, before the connection goes back to the pool
+struct PooledTransfer<T> {
+    transfer: T,
+    connection: PooledConnection,
+}
+
+impl<T> PooledTransfer<T> {
+    fn track<U>(&mut self, poll: Poll<io::Result<U>>) -> Poll<io::Result<U>> {
+        if let Poll::Ready(Err(error)) = &poll {
+            self.connection.broken = matches!(
+                network_io_error_kind(error.kind()),
+                ErrorKind::ConnectionLost | ErrorKind::Timeout
+            );
+        }
+
+        poll
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for PooledTransfer<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.transfer).poll_read(cx, buf);
+        self.track(poll)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for PooledTransfer<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.transfer).poll_write(cx, buf);
+        self.track(poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.transfer).poll_flush(cx);
+        self.track(poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.transfer).poll_shutdown(cx);
+        self.track(poll)
+    }
+}
@@ -0,0 +1,52 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::backends::retry::with_backoff;
+use crate::error::{ErrorKind, Result};
+
+// How a backend re-establishes a connection it lost, attempts are spaced out like retries
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 0,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) async fn reconnect<T, Fut: Future<Output = Result<T>>>(
+        &self,
+        connect: impl FnMut() -> Fut,
+    ) -> Result<T> {
+        with_backoff(
+            self.max_attempts,
+            self.initial_backoff,
+            self.max_backoff,
+            connect,
+            // Trying again cannot fix rejected credentials or host keys
+            |error| {
+                !matches!(
+                    error.kind(),
+                    ErrorKind::AuthenticationFailed | ErrorKind::HostKeyRejected
+                )
+            },
+        )
+        .await
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::data::{Capabilities, File, FileType, Metadata};
+use crate::error::{Error, Result};
+use crate::{FSBackend, FileReader, FileWriter};
+
+// How often and how far apart failed operations are attempted again, the doubling backoff is
+// randomized by up to half of its value so that concurrent clients do not retry in lockstep
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) async fn retry<T, Fut: Future<Output = Result<T>>>(
+        &self,
+        operation: impl FnMut() -> Fut,
+    ) -> Result<T> {
+        with_backoff(
+            self.max_attempts,
+            self.initial_backoff,
+            self.max_backoff,
+            operation,
+            Error::is_transient_error,
+        )
+        .await
+    }
+}
+
+// Attempts `operation` until it succeeds, fails with an error `should_retry` rejects or runs out
+// of attempts. Retries and reconnects share this schedule, the backoff doubles after every
+// attempt and is randomized as described on `RetryPolicy`.
+pub(crate) async fn with_backoff<T, Fut: Future<Output = Result<T>>>(
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    mut operation: impl FnMut() -> Fut,
+    should_retry: impl Fn(&Error) -> bool,
+) -> Result<T> {
+    let mut backoff = initial_backoff;
+    let mut attempt = 1;
+
+    loop {
+        match operation().await {
+            Err(error) if attempt < max_attempts && should_retry(&error) => {
+                tokio::time::sleep(jittered(backoff)).await;
+                backoff = (backoff * 2).min(max_backoff);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn jittered(backoff: Duration) -> Duration {
+    // A freshly seeded hasher is random enough for spreading out retries
+    let random = RandomState::new().build_hasher().finish();
+    backoff / 2 + backoff.mul_f64((random % 1000) as f64 / 2000.0)
+}
+
+// Wraps a backend and retries operations that failed with a transient error. Only operations
+// that can safely be repeated are retried, the others are passed through unchanged
+pub struct RetryBackend<B: FSBackend> {
+    backend: B,
+    policy: RetryPolicy,
+}
+
+impl<B: FSBackend> RetryBackend<B> {
+    pub fn new(backend: B, policy: RetryPolicy) -> Self {
+        Self { backend, policy }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn unwrap(self) -> B {
+        self.backend
+    }
+}
+
+#[async_trait]
+impl<B: FSBackend> FSBackend for RetryBackend<B> {
+    fn capabilities(&self) -> Capabilities {
+        self.backend.capabilities()
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        self.backend.disconnect().await
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        self.policy.retry(|| self.backend.exists(path)).await
+    }
+
+    async fn get_file_type(&self, path: &str) -> Result<FileType> {
+        self.policy.retry(|| self.backend.get_file_type(path)).await
+    }
+
+    async fn retrieve_files(&self, paths: &[&str], follow_symlinks: bool) -> Result<Vec<File>> {
+        self.policy
+            .retry(|| self.backend.retrieve_files(paths, follow_symlinks))
+            .await
+    }
+
+    async fn symlink_metadata(&self, path: &str) -> Result<Metadata> {
+        self.policy
+            .retry(|| self.backend.symlink_metadata(path))
+            .await
+    }
+
+    async fn retrieve_file_content(&self, path: &str) -> Result<Vec<u8>> {
+        self.policy
+            .retry(|| self.backend.retrieve_file_content(path))
+            .await
+    }
+
+    async fn read_dir(&self, path: &str) -> Result<Vec<File>> {
+        self.policy.retry(|| self.backend.read_dir(path)).await
+    }
+
+    async fn create_file(
+        &self,
+        path: &str,
+        overwrite: bool,
+        contents: Option<&[u8]>,
+    ) -> Result<()> {
+        // Without overwrite, a repeated attempt fails if the first one got through
+        if !overwrite {
+            return self.backend.create_file(path, overwrite, contents).await;
+        }
+
+        self.policy
+            .retry(|| self.backend.create_file(path, overwrite, contents))
+            .await
+    }
+
+    async fn append_file(&self, path: &str, contents: &[u8]) -> Result<()> {
+        self.backend.append_file(path, contents).await
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<()> {
+        self.backend.create_dir(path).await
+    }
+
+    async fn rename_file(&self, path: &str, new_name: &str, overwrite: bool) -> Result<()> {
+        self.backend.rename_file(path, new_name, overwrite).await
+    }
+
+    async fn move_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        self.backend.move_file(from, to, overwrite).await
+    }
+
+    async fn copy_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        if !overwrite {
+            return self.backend.copy_file(from, to, overwrite).await;
+        }
+
+        self.policy
+            .retry(|| self.backend.copy_file(from, to, overwrite))
+            .await
+    }
+
+    async fn remove_file(&self, path: &str) -> Result<()> {
+        self.backend.remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &str) -> Result<()> {
+        self.backend.remove_dir(path).await
+    }
+
+    async fn trash(&self, paths: &[&str]) -> Result<()> {
+        self.backend.trash(paths).await
+    }
+
+    async fn set_file_permissions_unix(&self, path: &str, mode: u32) -> Result<()> {
+        self.policy
+            .retry(|| self.backend.set_file_permissions_unix(path, mode))
+            .await
+    }
+
+    async fn set_owner(&self, path: &str, uid: u32, gid: u32) -> Result<()> {
+        self.policy
+            .retry(|| self.backend.set_owner(path, uid, gid))
+            .await
+    }
+
+    async fn read_link(&self, path: &str) -> Result<String> {
+        self.policy.retry(|| self.backend.read_link(path)).await
+    }
+
+    async fn create_symlink(&self, path: &str, target: &str) -> Result<()> {
+        self.backend.create_symlink(path, target).await
+    }
+
+    async fn create_hard_link(&self, path: &str, target: &str) -> Result<()> {
+        self.backend.create_hard_link(path, target).await
+    }
+
+    async fn open_write(&self, path: &str, overwrite: bool) -> Result<FileWriter> {
+        if !overwrite {
+            return self.backend.open_write(path, overwrite).await;
+        }
+
+        self.policy
+            .retry(|| self.backend.open_write(path, overwrite))
+            .await
+    }
+
+    // Only opening is retried, a failure while reading is left to the caller
+    async fn open_read(&self, path: &str) -> Result<FileReader> {
+        self.policy.retry(|| self.backend.open_read(path)).await
+    }
+
+    async fn read_range(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        self.policy
+            .retry(|| self.backend.read_range(path, offset, len))
+            .await
+    }
+}
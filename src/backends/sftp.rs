@@ -1,96 +1,563 @@
-use std::path::Path;
+use std::future::{poll_fn, Future};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use russh::client::{DisconnectReason, Handle};
+use russh::keys::agent::client::AgentClient;
+use russh::keys::agent::AgentIdentity;
+use russh::keys::known_hosts::{learn_known_hosts, learn_known_hosts_path};
+use russh::keys::{
+    check_known_hosts, check_known_hosts_path, load_secret_key, PrivateKeyWithHashAlg, PublicKey,
+    PublicKeyOrCertificate,
+};
+use russh::Disconnect;
+use russh_sftp::client::error::Error as SFTPError;
 use russh_sftp::client::fs::Metadata as SFTPMetadata;
-use russh_sftp::client::SftpSession;
-use tokio::io::AsyncWriteExt;
-
-use crate::data::{File, FileType, Metadata};
-use crate::error::{Error, Result};
+use russh_sftp::client::{RawSftpSession, SftpSession};
+use russh_sftp::extensions;
+use russh_sftp::protocol::{OpenFlags, StatusCode};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{watch, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::backends::reconnect::ReconnectPolicy;
+use crate::data::{Capabilities, File, FileType, Metadata};
+use crate::error::{Error, ErrorKind, Result, ResultExt};
 use crate::util::remove_lowest_path_item;
-use crate::FSBackend;
+use crate::{FSBackend, FileReader, FileWriter};
 
 pub struct SFTPBackend {
-    pub session: SftpSession,
+    connection: RwLock<SFTPConnection>,
+    // Only present when the backend was built by `SFTPBuilder`, which allows reconnecting
+    builder: Option<SFTPBuilder>,
+    hard_links: bool,
+}
+
+struct SFTPConnection {
+    session: Arc<SftpSession>,
+    // Only present when the backend established the SSH connection itself
+    ssh: Option<Handle<SSHClient>>,
+    // A second channel for listing directories, as `SftpSession` drops the longnames of entries,
+    // which are the only place version 3 of the protocol names their owners
+    listing: Option<Arc<RawSftpSession>>,
+    // Changes to true once the SSH session has ended, requests in flight are not failed by it
+    closed: Option<watch::Receiver<bool>>,
+    // Whether the server offered hardlink@openssh.com when the session was initialized
+    hard_links: bool,
+}
+
+impl SFTPConnection {
+    fn is_lost(&self) -> bool {
+        self.ssh.as_ref().is_some_and(Handle::is_closed)
+    }
 }
 
 impl SFTPBackend {
     pub fn new(session: SftpSession) -> Self {
-        Self { session }
+        Self {
+            connection: RwLock::new(SFTPConnection {
+                session: Arc::new(session),
+                ssh: None,
+                listing: None,
+                closed: None,
+                hard_links: false,
+            }),
+            builder: None,
+            // The extensions of an existing session cannot be told
+            hard_links: false,
+        }
+    }
+
+    pub fn connect(host: impl Into<String>, user: impl Into<String>) -> SFTPBuilder {
+        SFTPBuilder {
+            host: host.into(),
+            port: 22,
+            user: user.into(),
+            auth: SFTPAuth::Agent,
+            host_key_policy: HostKeyPolicy::default(),
+            reconnect: ReconnectPolicy::default(),
+            keepalive: None,
+        }
     }
 
     pub fn inner(&mut self) -> &mut SftpSession {
-        &mut self.session
+        Arc::get_mut(&mut self.connection.get_mut().session)
+            .expect("SFTP session is still used by a running operation")
     }
 
     pub fn unwrap(self) -> SftpSession {
-        self.session
+        Arc::try_unwrap(self.connection.into_inner().session)
+            .ok()
+            .expect("SFTP session is still used by a running operation")
+    }
+
+    fn reconnecting_builder(&self) -> Option<&SFTPBuilder> {
+        self.builder
+            .as_ref()
+            .filter(|builder| builder.reconnect.max_attempts > 0)
+    }
+
+    // The current session, after reconnecting if the connection was lost
+    pub async fn session(&self) -> Result<Arc<SftpSession>> {
+        Ok(self.connected().await?.session.clone())
+    }
+
+    async fn connected(&self) -> Result<RwLockReadGuard<'_, SFTPConnection>> {
+        {
+            let connection = self.connection.read().await;
+            if !connection.is_lost() || self.reconnecting_builder().is_none() {
+                return Ok(connection);
+            }
+        }
+
+        let mut connection = self.connection.write().await;
+        // Another operation may have reconnected while this one waited for the lock
+        if let (true, Some(builder)) = (connection.is_lost(), self.reconnecting_builder()) {
+            *connection = builder
+                .reconnect
+                .reconnect(|| builder.connect_session())
+                .await?;
+        }
+
+        Ok(RwLockWriteGuard::downgrade(connection))
+    }
+
+    // Replaces `lost` with a new session, unless another operation already did
+    async fn reconnect(&self, lost: &Arc<SftpSession>) -> Result<()> {
+        let mut connection = self.connection.write().await;
+        if let (true, Some(builder)) = (
+            Arc::ptr_eq(&connection.session, lost),
+            self.reconnecting_builder(),
+        ) {
+            *connection = builder
+                .reconnect
+                .reconnect(|| builder.connect_session())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // The SSH session may not have noticed a dropped connection yet when an operation starts, the
+    // operation is then given up once it does and retried once on a new session
+    async fn with_session<T, F, Fut>(
+        &self,
+        operation: &'static str,
+        paths: &[&str],
+        run: F,
+    ) -> Result<T>
+    where
+        F: Fn(Arc<SftpSession>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let (session, closed) = {
+            let connection = self.connected().await.context(operation, paths)?;
+            (connection.session.clone(), connection.closed.clone())
+        };
+
+        match until_closed(run(session.clone()), closed).await {
+            Err(error)
+                if error.kind() == ErrorKind::ConnectionLost
+                    && self.reconnecting_builder().is_some() =>
+            {
+                self.reconnect(&session).await.context(operation, paths)?;
+                run(self.session().await.context(operation, paths)?).await
+            }
+            result => result,
+        }
+        .context(operation, paths)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SFTPAuth {
+    Password(String),
+    PrivateKey {
+        path: PathBuf,
+        passphrase: Option<String>,
+    },
+    // Tries every identity offered by the agent at `SSH_AUTH_SOCK`
+    Agent,
+}
+
+#[derive(Clone)]
+pub struct SFTPBuilder {
+    host: String,
+    port: u16,
+    user: String,
+    auth: SFTPAuth,
+    host_key_policy: HostKeyPolicy,
+    reconnect: ReconnectPolicy,
+    keepalive: Option<Duration>,
+}
+
+impl SFTPBuilder {
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.auth = SFTPAuth::Password(password.into());
+        self
+    }
+
+    pub fn private_key(mut self, path: impl Into<PathBuf>, passphrase: Option<&str>) -> Self {
+        self.auth = SFTPAuth::PrivateKey {
+            path: path.into(),
+            passphrase: passphrase.map(|passphrase| passphrase.to_string()),
+        };
+        self
+    }
+
+    pub fn agent(mut self) -> Self {
+        self.auth = SFTPAuth::Agent;
+        self
+    }
+
+    pub fn host_key_policy(mut self, policy: HostKeyPolicy) -> Self {
+        self.host_key_policy = policy;
+        self
+    }
+
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    // Sends SSH keepalive requests whenever the server has been silent for `interval`
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    pub async fn build(self) -> Result<SFTPBackend> {
+        let connection = self.connect_session().await?;
+
+        Ok(SFTPBackend {
+            hard_links: connection.hard_links,
+            connection: RwLock::new(connection),
+            builder: Some(self),
+        })
+    }
+
+    async fn connect_session(&self) -> Result<SFTPConnection> {
+        let config = russh::client::Config {
+            keepalive_interval: self.keepalive,
+            ..Default::default()
+        };
+        let (closed_sender, closed) = watch::channel(false);
+        let mut ssh = russh::client::connect(
+            Arc::new(config),
+            (self.host.as_str(), self.port),
+            SSHClient {
+                host: self.host.clone(),
+                port: self.port,
+                host_key_policy: self.host_key_policy.clone(),
+                closed: closed_sender,
+            },
+        )
+        .await?;
+
+        if !authenticate(&mut ssh, &self.user, &self.auth).await? {
+            return Err(Error::AuthenticationFailed(self.user.clone()));
+        }
+
+        let channel = ssh.channel_open_session().await?;
+        channel.request_subsystem(true, "sftp").await?;
+        let session = SftpSession::new(channel.into_stream()).await?;
+
+        let channel = ssh.channel_open_session().await?;
+        channel.request_subsystem(true, "sftp").await?;
+        let listing = RawSftpSession::new(channel.into_stream());
+        let version = listing.init().await?;
+
+        Ok(SFTPConnection {
+            session: Arc::new(session),
+            ssh: Some(ssh),
+            listing: Some(Arc::new(listing)),
+            closed: Some(closed),
+            hard_links: version
+                .extensions
+                .get(extensions::HARDLINK)
+                .is_some_and(|version| version == "1"),
+        })
+    }
+}
+
+async fn authenticate(ssh: &mut Handle<SSHClient>, user: &str, auth: &SFTPAuth) -> Result<bool> {
+    match auth {
+        SFTPAuth::Password(password) => {
+            Ok(ssh.authenticate_password(user, password).await?.success())
+        }
+        SFTPAuth::PrivateKey { path, passphrase } => {
+            let key = load_secret_key(path, passphrase.as_deref())?;
+            let hash_alg = ssh.best_supported_rsa_hash().await?.flatten();
+
+            Ok(ssh
+                .authenticate_publickey(user, PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg))
+                .await?
+                .success())
+        }
+        SFTPAuth::Agent => {
+            let mut agent = AgentClient::connect_env().await?;
+            let hash_alg = ssh.best_supported_rsa_hash().await?.flatten();
+
+            for identity in agent.request_identities().await? {
+                // Certificates need a different authentication method, only plain keys are tried
+                let AgentIdentity::PublicKey { key, .. } = identity else {
+                    continue;
+                };
+
+                if ssh
+                    .authenticate_publickey_with(user, key, hash_alg, &mut agent)
+                    .await?
+                    .success()
+                {
+                    return Ok(true);
+                }
+            }
+
+            Ok(false)
+        }
+    }
+}
+
+pub type HostKeyCallback = dyn Fn(&str, u16, &PublicKey) -> bool + Send + Sync;
+
+#[derive(Clone)]
+pub enum HostKeyPolicy {
+    // Only keys recorded in the known_hosts file are accepted, `None` uses ~/.ssh/known_hosts
+    KnownHosts(Option<PathBuf>),
+    // Unknown hosts are accepted and recorded in the known_hosts file, changed keys are rejected
+    TrustOnFirstUse(Option<PathBuf>),
+    // Receives the host, port and key of the server and returns whether to trust it
+    Custom(Arc<HostKeyCallback>),
+}
+
+impl Default for HostKeyPolicy {
+    fn default() -> Self {
+        Self::KnownHosts(None)
+    }
+}
+
+impl HostKeyPolicy {
+    pub fn verify(&self, host: &str, port: u16, key: &PublicKey) -> Result<()> {
+        let trusted = match self {
+            Self::KnownHosts(path) => is_known_host(host, port, key, path.as_deref())?,
+            Self::TrustOnFirstUse(path) => {
+                if !is_known_host(host, port, key, path.as_deref())? {
+                    match path {
+                        Some(path) => learn_known_hosts_path(host, port, key, path)?,
+                        None => learn_known_hosts(host, port, key)?,
+                    }
+                }
+
+                true
+            }
+            Self::Custom(callback) => callback(host, port, key),
+        };
+
+        if !trusted {
+            return Err(Error::HostKeyRejected(host.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+fn is_known_host(host: &str, port: u16, key: &PublicKey, path: Option<&Path>) -> Result<bool> {
+    let result = match path {
+        Some(path) => check_known_hosts_path(host, port, key, path),
+        None => check_known_hosts(host, port, key),
+    };
+
+    match result {
+        Err(russh::keys::Error::KeyChanged { .. }) => Err(Error::HostKeyMismatch(host.to_string())),
+        result => Ok(result?),
+    }
+}
+
+struct SSHClient {
+    host: String,
+    port: u16,
+    host_key_policy: HostKeyPolicy,
+    closed: watch::Sender<bool>,
+}
+
+impl russh::client::Handler for SSHClient {
+    type Error = Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKeyOrCertificate,
+    ) -> Result<bool> {
+        match server_public_key {
+            PublicKeyOrCertificate::PublicKey { key, .. } => {
+                self.host_key_policy.verify(&self.host, self.port, key)?;
+                Ok(true)
+            }
+            // Host certificates would need a trusted certificate authority to check against
+            PublicKeyOrCertificate::Certificate(_) => {
+                Err(Error::HostKeyRejected(self.host.clone()))
+            }
+        }
+    }
+
+    async fn disconnected(&mut self, reason: DisconnectReason<Error>) -> Result<()> {
+        self.closed.send_replace(true);
+
+        match reason {
+            DisconnectReason::ReceivedDisconnect(_) => Ok(()),
+            DisconnectReason::Error(error) => Err(error),
+        }
     }
 }
 
 #[async_trait]
 impl FSBackend for SFTPBackend {
+    fn capabilities(&self) -> Capabilities {
+        let capabilities = Capabilities::SET_PERMISSIONS
+            | Capabilities::SYMLINKS
+            | Capabilities::SET_OWNER
+            | Capabilities::ATOMIC_RENAME
+            | Capabilities::RANGED_READS
+            | Capabilities::APPEND
+            | Capabilities::MODIFIED_TIME
+            | Capabilities::ACCESSED_TIME;
+
+        if self.hard_links {
+            capabilities | Capabilities::HARD_LINKS
+        } else {
+            capabilities
+        }
+    }
+
     async fn disconnect(&self) -> Result<()> {
-        self.session.close().await?;
+        let connection = self.connection.read().await;
+        connection.session.close().await?;
+        if let Some(listing) = &connection.listing {
+            listing.close_session()?;
+        }
+
+        if let Some(ssh) = &connection.ssh {
+            ssh.disconnect(Disconnect::ByApplication, "", "en").await?;
+        }
+
         Ok(())
     }
     async fn exists(&self, path: &str) -> Result<bool> {
-        Ok(self.session.try_exists(path).await?)
+        self.with_session("exists", &[path], |session| async move {
+            Ok(session.try_exists(path).await?)
+        })
+        .await
     }
 
     async fn get_file_type(&self, path: &str) -> Result<FileType> {
-        Ok(file_type_from_sftp_metadata(
-            &self.session.metadata(path).await?,
-        ))
+        self.with_session("get_file_type", &[path], |session| async move {
+            Ok(file_type_from_sftp_metadata(&session.metadata(path).await?))
+        })
+        .await
     }
 
-    async fn retrieve_files(&self, paths: &[&str]) -> Result<Vec<File>> {
-        let mut files = vec![];
-
-        for path in paths {
-            let path_std = Path::new(&path);
-
-            files.push(File {
-                path: path.to_string(),
-                name: path_std
-                    .file_name()
-                    .ok_or(Error::NoFileName)?
-                    .to_str()
-                    .unwrap() // Input paths are already Unicode
-                    .to_string(),
-                extension: path_std
-                    .extension()
-                    .and_then(|os_str| Some(os_str.to_str().unwrap().to_lowercase())), // Input paths are already Unicode
-                metadata: self.session.metadata(*path).await?.into(),
-            })
-        }
+    async fn retrieve_files(&self, paths: &[&str], follow_symlinks: bool) -> Result<Vec<File>> {
+        self.with_session("retrieve_files", paths, |session| async move {
+            let mut files = vec![];
+
+            for path in paths {
+                let path_std = Path::new(&path);
+
+                files.push(File {
+                    path: path.to_string(),
+                    name: path_std
+                        .file_name()
+                        .ok_or(Error::NoFileName)
+                        .context("retrieve_files", &[path])?
+                        .to_str()
+                        .unwrap() // Input paths are already Unicode
+                        .to_string(),
+                    extension: path_std
+                        .extension()
+                        .map(|os_str| os_str.to_str().unwrap().to_lowercase()), // Input paths are already Unicode
+                    metadata: if follow_symlinks {
+                        session
+                            .metadata(*path)
+                            .await
+                            .context("retrieve_files", &[path])?
+                            .into()
+                    } else {
+                        lstat(&session, path)
+                            .await
+                            .context("retrieve_files", &[path])?
+                    },
+                })
+            }
+
+            Ok(files)
+        })
+        .await
+    }
 
-        Ok(files)
+    async fn symlink_metadata(&self, path: &str) -> Result<Metadata> {
+        self.with_session("symlink_metadata", &[path], |session| async move {
+            lstat(&session, path).await
+        })
+        .await
     }
 
     async fn retrieve_file_content(&self, path: &str) -> Result<Vec<u8>> {
-        Ok(self.session.read(path).await?)
+        self.with_session("retrieve_file_content", &[path], |session| async move {
+            Ok(session.read(path).await?)
+        })
+        .await
     }
 
     async fn read_dir(&self, path: &str) -> Result<Vec<File>> {
-        Ok(self
-            .session
-            .read_dir(path)
-            .await?
-            .map(|file| {
-                let path = format!("{path}/{}", file.file_name());
-                let extension = Path::new(&path)
-                    .extension()
-                    .and_then(|os_str| os_str.to_str().and_then(|str| Some(str.to_lowercase())));
-
-                File {
-                    path,
-                    name: file.file_name(),
-                    extension,
-                    metadata: file.metadata().into(),
-                }
+        let (session, entries) = self
+            .with_session("read_dir", &[path], |session| async move {
+                let listing = self.connected().await?.listing.clone();
+                let entries = match listing {
+                    Some(listing) => read_dir_with_longnames(&listing, path).await?,
+                    None => session
+                        .read_dir(path)
+                        .await?
+                        .map(|entry| (entry.file_name(), None, entry.metadata()))
+                        .collect(),
+                };
+
+                Ok((session, entries))
             })
-            .collect())
+            .await?;
+        let mut files = vec![];
+
+        for (name, longname, sftp_metadata) in entries {
+            let file_path = format!("{path}/{name}");
+            let extension = Path::new(&file_path)
+                .extension()
+                .and_then(|os_str| os_str.to_str().map(|str| str.to_lowercase()));
+
+            let mut metadata: Metadata = sftp_metadata.into();
+            if let Some(longname) = longname {
+                (metadata.user, metadata.group) = owner_names(&longname);
+            }
+            // A link that cannot be read is still listed, only without its target
+            if metadata.r#type == FileType::Symlink {
+                metadata.link_target = session.read_link(&file_path).await.ok();
+            }
+
+            files.push(File {
+                path: file_path,
+                name,
+                extension,
+                metadata,
+            });
+        }
+
+        Ok(files)
     }
 
     async fn create_file(
@@ -99,62 +566,117 @@ impl FSBackend for SFTPBackend {
         overwrite: bool,
         contents: Option<&[u8]>,
     ) -> Result<()> {
-        if !overwrite && self.exists(&path).await? {
-            return Err(Error::FileAlreadyExists(path.to_string()));
+        if !overwrite && self.exists(path).await? {
+            return Err(Error::FileAlreadyExists(path.to_string()).context("create_file", &[path]));
         }
 
-        let mut file = self.session.create(path).await?;
+        let mut file = self
+            .with_session("create_file", &[path], |session| async move {
+                Ok(session.create(path).await?)
+            })
+            .await?;
 
         if let Some(contents) = contents {
-            file.write_all(contents).await?;
+            file.write_all(contents)
+                .await
+                .context("create_file", &[path])?;
         }
 
+        file.shutdown().await.context("create_file", &[path])?;
         Ok(())
     }
 
-    async fn create_dir(&self, path: &str) -> Result<()> {
-        self.session.create_dir(path).await?;
+    async fn append_file(&self, path: &str, contents: &[u8]) -> Result<()> {
+        let mut file = self
+            .with_session("append_file", &[path], |session| async move {
+                Ok(session
+                    .open_with_flags(
+                        path,
+                        OpenFlags::CREATE | OpenFlags::APPEND | OpenFlags::WRITE,
+                    )
+                    .await?)
+            })
+            .await?;
+
+        // Not every server honours the append flag, so write at the current end ourselves
+        file.seek(SeekFrom::End(0))
+            .await
+            .context("append_file", &[path])?;
+        file.write_all(contents)
+            .await
+            .context("append_file", &[path])?;
+        file.shutdown().await.context("append_file", &[path])?;
         Ok(())
     }
 
+    async fn create_dir(&self, path: &str) -> Result<()> {
+        // SFTPv3 has no dedicated status code for existing files, servers reply with a generic failure
+        if self.exists(path).await? {
+            return Err(Error::FileAlreadyExists(path.to_string()).context("create_dir", &[path]));
+        }
+
+        self.with_session("create_dir", &[path], |session| async move {
+            Ok(session.create_dir(path).await?)
+        })
+        .await
+    }
+
     async fn rename_file(&self, path: &str, new_name: &str, overwrite: bool) -> Result<()> {
-        let new_path = format!("{}/{new_name}", remove_lowest_path_item(path));
+        let new_path = &format!("{}/{new_name}", remove_lowest_path_item(path));
 
-        if !overwrite && self.exists(&new_path).await? {
-            return Err(Error::FileAlreadyExists(new_path));
+        if !overwrite && self.exists(new_path).await? {
+            return Err(Error::FileAlreadyExists(new_path.clone())
+                .context("rename_file", &[path, new_path]));
         }
 
-        self.session.rename(path, new_path).await?;
-        Ok(())
+        self.with_session("rename_file", &[path, new_path], |session| async move {
+            Ok(session.rename(path, new_path).await?)
+        })
+        .await
     }
 
     async fn move_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
-        if !overwrite && self.exists(&to).await? {
-            return Err(Error::FileAlreadyExists(to.to_string()));
+        if !overwrite && self.exists(to).await? {
+            return Err(Error::FileAlreadyExists(to.to_string()).context("move_file", &[from, to]));
         }
 
-        self.session.rename(from, to).await?;
-        Ok(())
+        self.with_session("move_file", &[from, to], |session| async move {
+            Ok(session.rename(from, to).await?)
+        })
+        .await
     }
 
     async fn copy_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
-        let metadata = self.session.metadata(from).await?;
-        let contents = self.retrieve_file_content(from).await?;
-
-        self.create_file(to, overwrite, Some(&contents)).await?;
-        self.session.set_metadata(to, metadata).await?;
-
-        Ok(())
+        let metadata = &self
+            .with_session("copy_file", &[from, to], |session| async move {
+                Ok(session.metadata(from).await?)
+            })
+            .await?;
+        let mut reader = self.open_read(from).await?;
+        let mut writer = self.open_write(to, overwrite).await?;
+
+        tokio::io::copy(&mut reader, &mut writer)
+            .await
+            .context("copy_file", &[from, to])?;
+        writer.shutdown().await.context("copy_file", &[from, to])?;
+        self.with_session("copy_file", &[from, to], |session| async move {
+            Ok(session.set_metadata(to, metadata.clone()).await?)
+        })
+        .await
     }
 
     async fn remove_file(&self, path: &str) -> Result<()> {
-        self.session.remove_file(path).await?;
-        Ok(())
+        self.with_session("remove_file", &[path], |session| async move {
+            Ok(session.remove_file(path).await?)
+        })
+        .await
     }
 
     async fn remove_dir(&self, path: &str) -> Result<()> {
-        self.session.remove_dir(path).await?;
-        Ok(())
+        self.with_session("remove_dir", &[path], |session| async move {
+            Ok(session.remove_dir(path).await?)
+        })
+        .await
     }
 
     async fn trash(&self, _paths: &[&str]) -> Result<()> {
@@ -162,17 +684,187 @@ impl FSBackend for SFTPBackend {
     }
 
     async fn set_file_permissions_unix(&self, path: &str, mode: u32) -> Result<()> {
-        self.session
-            .set_metadata(
-                path,
-                russh_sftp::protocol::FileAttributes {
-                    permissions: Some(mode),
-                    ..Default::default()
-                },
-            )
+        self.with_session("set_file_permissions_unix", &[path], |session| async move {
+            Ok(session
+                .set_metadata(
+                    path,
+                    russh_sftp::protocol::FileAttributes {
+                        permissions: Some(mode),
+                        ..Default::default()
+                    },
+                )
+                .await?)
+        })
+        .await
+    }
+
+    async fn set_owner(&self, path: &str, uid: u32, gid: u32) -> Result<()> {
+        self.with_session("set_owner", &[path], |session| async move {
+            Ok(session
+                .set_metadata(
+                    path,
+                    russh_sftp::protocol::FileAttributes {
+                        uid: Some(uid),
+                        gid: Some(gid),
+                        ..Default::default()
+                    },
+                )
+                .await?)
+        })
+        .await
+    }
+
+    async fn read_link(&self, path: &str) -> Result<String> {
+        self.with_session("read_link", &[path], |session| async move {
+            Ok(session.read_link(path).await?)
+        })
+        .await
+    }
+
+    async fn create_symlink(&self, path: &str, target: &str) -> Result<()> {
+        self.with_session("create_symlink", &[path], |session| async move {
+            // As with directories, servers reply with a generic failure for existing files
+            if session.symlink_metadata(path).await.is_ok() {
+                return Err(Error::FileAlreadyExists(path.to_string()));
+            }
+
+            // OpenSSH swapped the arguments of SSH_FXP_SYMLINK by mistake and other servers followed
+            Ok(session.symlink(target, path).await?)
+        })
+        .await
+    }
+
+    async fn create_hard_link(&self, path: &str, target: &str) -> Result<()> {
+        self.with_session("create_hard_link", &[target, path], |session| async move {
+            if session.symlink_metadata(path).await.is_ok() {
+                return Err(Error::FileAlreadyExists(path.to_string()));
+            }
+
+            if !session.hardlink(target, path).await? {
+                return Err(Error::Unsupported(
+                    "create_hard_link".into(),
+                    "SFTP (Server lacks hardlink@openssh.com)".into(),
+                ));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn open_write(&self, path: &str, overwrite: bool) -> Result<FileWriter> {
+        if !overwrite && self.exists(path).await? {
+            return Err(Error::FileAlreadyExists(path.to_string()).context("open_write", &[path]));
+        }
+
+        Ok(Box::new(
+            self.with_session("open_write", &[path], |session| async move {
+                Ok(session.create(path).await?)
+            })
+            .await?,
+        ))
+    }
+
+    async fn open_read(&self, path: &str) -> Result<FileReader> {
+        Ok(Box::new(
+            self.with_session("open_read", &[path], |session| async move {
+                Ok(session.open(path).await?)
+            })
+            .await?,
+        ))
+    }
+
+    async fn read_range(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut file = self
+            .with_session("read_range", &[path], |session| async move {
+                Ok(session.open(path).await?)
+            })
             .await?;
-        Ok(())
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .context("read_range", &[path])?;
+
+        let mut contents = vec![];
+        file.take(len)
+            .read_to_end(&mut contents)
+            .await
+            .context("read_range", &[path])?;
+        Ok(contents)
+    }
+}
+
+// Pending requests are only failed by their timeout when the connection is lost, so they are
+// abandoned as soon as the SSH session ends instead
+async fn until_closed<T>(
+    operation: impl Future<Output = Result<T>>,
+    closed: Option<watch::Receiver<bool>>,
+) -> Result<T> {
+    let Some(mut closed) = closed else {
+        return operation.await;
+    };
+
+    let mut operation = pin!(operation);
+    // The sender is dropped along with the session, which ends it just the same
+    let mut closed = pin!(closed.wait_for(|closed| *closed));
+    poll_fn(|cx| {
+        if let Poll::Ready(result) = operation.as_mut().poll(cx) {
+            return Poll::Ready(result);
+        }
+
+        closed
+            .as_mut()
+            .poll(cx)
+            .map(|_| Err(russh::Error::Disconnect.into()))
+    })
+    .await
+}
+
+// Like `SftpSession::read_dir`, but keeps the longname of every entry
+async fn read_dir_with_longnames(
+    listing: &RawSftpSession,
+    path: &str,
+) -> std::result::Result<Vec<(String, Option<String>, SFTPMetadata)>, SFTPError> {
+    let handle = listing.opendir(path).await?.handle;
+    let mut entries = vec![];
+
+    loop {
+        match listing.readdir(handle.as_str()).await {
+            Ok(name) => entries.extend(
+                name.files
+                    .into_iter()
+                    .filter(|file| file.filename != "." && file.filename != "..")
+                    .map(|file| (file.filename, Some(file.longname), file.attrs)),
+            ),
+            Err(SFTPError::Status(status)) if status.status_code == StatusCode::Eof => break,
+            Err(error) => return Err(error),
+        }
     }
+
+    listing.close(handle).await?;
+    Ok(entries)
+}
+
+// Servers format longnames like `ls -l`, the owner and group following the permissions and link
+// count. Numbers in their place mean the server does not know the names.
+fn owner_names(longname: &str) -> (Option<String>, Option<String>) {
+    let mut fields = longname.split_whitespace().skip(2);
+    let mut name = || {
+        fields
+            .next()
+            .filter(|field| field.parse::<u32>().is_err())
+            .map(String::from)
+    };
+
+    (name(), name())
+}
+
+async fn lstat(session: &SftpSession, path: &str) -> Result<Metadata> {
+    let mut metadata: Metadata = session.symlink_metadata(path).await?.into();
+    if metadata.r#type == FileType::Symlink {
+        // As in `read_dir`, a link that cannot be read is reported without its target
+        metadata.link_target = session.read_link(path).await.ok();
+    }
+
+    Ok(metadata)
 }
 
 impl From<SFTPMetadata> for Metadata {
@@ -186,20 +878,33 @@ impl From<SFTPMetadata> for Metadata {
             readonly: false, // FIXME: Assumption
             unix_mode: sftp_metadata
                 .permissions
-                .and_then(|permission_bits| Some(permission_bits.into())),
+                .map(|permission_bits| permission_bits.into()),
+            link_target: None,
+            // Version 3 of the protocol has no attributes for these
+            link_count: None,
+            inode: None,
+            device: None,
+            uid: sftp_metadata.uid,
+            gid: sftp_metadata.gid,
+            // Only known from the longnames of directory listings, see `owner_names`
+            user: None,
+            group: None,
         }
     }
 }
 
 pub fn file_type_from_sftp_metadata(sftp_metadata: &SFTPMetadata) -> FileType {
-    // FIXME: Should we extract all these file_type() calls into a variable?
+    // russh-sftp tests the type bits individually, which makes e.g. every symlink a regular file
+    // too, so the type is compared as a whole instead
+    let file_type = sftp_metadata.permissions.unwrap_or_default() & 0o170000;
+
     FileType::from_complex_bools((
-        sftp_metadata.is_regular(),
-        sftp_metadata.is_dir(),
-        sftp_metadata.is_symlink(),
-        false,
-        sftp_metadata.is_fifo(),
-        sftp_metadata.is_character(),
-        sftp_metadata.is_block(),
+        file_type == 0o100000,
+        file_type == 0o040000,
+        file_type == 0o120000,
+        file_type == 0o140000,
+        file_type == 0o010000,
+        file_type == 0o020000,
+        file_type == 0o060000,
     ))
 }
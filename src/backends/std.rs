@@ -1,18 +1,41 @@
 use std::fs::Metadata as StdMetadata;
-use std::path::Path;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
-use crate::data::{File, FileType, Metadata};
-use crate::error::{Error, Result};
+use crate::data::{Capabilities, File, FileType, Metadata};
+use crate::error::{Error, Result, ResultExt};
 use crate::util::remove_lowest_path_item;
-use crate::FSBackend;
+use crate::{FSBackend, FileReader, FileWriter};
 
 pub struct StdBackend;
 
 #[async_trait]
 impl FSBackend for StdBackend {
+    fn capabilities(&self) -> Capabilities {
+        let capabilities = Capabilities::TRASH
+            | Capabilities::SERVER_SIDE_COPY
+            | Capabilities::ATOMIC_RENAME
+            | Capabilities::HARD_LINKS
+            | Capabilities::RANGED_READS
+            | Capabilities::APPEND
+            | Capabilities::MODIFIED_TIME
+            | Capabilities::ACCESSED_TIME
+            | Capabilities::CREATED_TIME;
+
+        if cfg!(unix) {
+            capabilities
+                | Capabilities::SET_PERMISSIONS
+                | Capabilities::SYMLINKS
+                | Capabilities::SET_OWNER
+        } else {
+            capabilities
+        }
+    }
+
     async fn disconnect(&self) -> Result<()> {
         // NOOP
         Ok(())
@@ -23,11 +46,13 @@ impl FSBackend for StdBackend {
 
     async fn get_file_type(&self, path: &str) -> Result<FileType> {
         Ok(file_type_from_std_metadata(
-            &tokio::fs::metadata(path).await?,
+            &tokio::fs::metadata(path)
+                .await
+                .context("get_file_type", &[path])?,
         ))
     }
 
-    async fn retrieve_files(&self, paths: &[&str]) -> Result<Vec<File>> {
+    async fn retrieve_files(&self, paths: &[&str], follow_symlinks: bool) -> Result<Vec<File>> {
         let mut files = vec![];
 
         for path in paths {
@@ -37,42 +62,69 @@ impl FSBackend for StdBackend {
                 path: path.to_string(),
                 name: std_path
                     .file_name()
-                    .ok_or(Error::NoFileName)?
+                    .ok_or(Error::NoFileName)
+                    .context("retrieve_files", &[path])?
                     .to_str()
                     .unwrap() // Input paths are already Unicode
                     .to_string(),
                 extension: std_path
                     .extension()
-                    .and_then(|os_str| Some(os_str.to_str().unwrap().to_lowercase())), // Input paths are already Unicode
-                metadata: tokio::fs::metadata(path).await?.into(),
+                    .map(|os_str| os_str.to_str().unwrap().to_lowercase()), // Input paths are already Unicode
+                metadata: if follow_symlinks {
+                    tokio::fs::metadata(path)
+                        .await
+                        .context("retrieve_files", &[path])?
+                        .into()
+                } else {
+                    lstat(path).await.context("retrieve_files", &[path])?
+                },
             });
         }
 
         Ok(files)
     }
 
+    async fn symlink_metadata(&self, path: &str) -> Result<Metadata> {
+        lstat(path).await.context("symlink_metadata", &[path])
+    }
+
     async fn retrieve_file_content(&self, path: &str) -> Result<Vec<u8>> {
-        Ok(tokio::fs::read(path).await?)
+        tokio::fs::read(path)
+            .await
+            .context("retrieve_file_content", &[path])
     }
 
     async fn read_dir(&self, path: &str) -> Result<Vec<File>> {
         let mut files = vec![];
-        let mut result = fs::read_dir(path).await?;
+        let mut result = fs::read_dir(path).await.context("read_dir", &[path])?;
 
         // `let Ok()` implies this loop will end whenever an error is encountered (and not return it)
         while let Ok(Some(entry)) = result.next_entry().await {
+            let mut metadata: Metadata =
+                entry.metadata().await.context("read_dir", &[path])?.into();
+            // A link that cannot be read is still listed, only without its target
+            if metadata.r#type == FileType::Symlink {
+                metadata.link_target = link_target(fs::read_link(entry.path()).await).ok();
+            }
+
             files.push(File {
-                path: entry.path().to_str().ok_or(Error::NotUtf8)?.to_string(),
+                path: entry
+                    .path()
+                    .to_str()
+                    .ok_or(Error::NotUtf8)
+                    .context("read_dir", &[path])?
+                    .to_string(),
                 name: entry
                     .file_name()
                     .to_str()
-                    .ok_or(Error::NotUtf8)?
+                    .ok_or(Error::NotUtf8)
+                    .context("read_dir", &[path])?
                     .to_string(),
                 extension: entry
                     .path()
                     .extension()
-                    .and_then(|os_str| os_str.to_str().and_then(|str| Some(str.to_lowercase()))),
-                metadata: entry.metadata().await?.into(),
+                    .and_then(|os_str| os_str.to_str().map(|str| str.to_lowercase())),
+                metadata,
             });
         }
 
@@ -86,61 +138,94 @@ impl FSBackend for StdBackend {
         contents: Option<&[u8]>,
     ) -> Result<()> {
         if !overwrite && self.exists(path).await? {
-            return Err(Error::FileAlreadyExists(path.to_string()));
+            return Err(Error::FileAlreadyExists(path.to_string()).context("create_file", &[path]));
         }
 
-        tokio::fs::File::create(path).await?;
+        tokio::fs::File::create(path)
+            .await
+            .context("create_file", &[path])?;
         if let Some(contents) = contents {
-            tokio::fs::write(path, contents).await?;
+            tokio::fs::write(path, contents)
+                .await
+                .context("create_file", &[path])?;
         }
         Ok(())
     }
 
+    async fn append_file(&self, path: &str, contents: &[u8]) -> Result<()> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .context("append_file", &[path])?;
+
+        file.write_all(contents)
+            .await
+            .context("append_file", &[path])?;
+        // Tokio only hands the write to a blocking thread, it has to be flushed before returning
+        file.flush().await.context("append_file", &[path])?;
+        Ok(())
+    }
+
     async fn create_dir(&self, path: &str) -> Result<()> {
-        tokio::fs::create_dir(path).await?;
+        tokio::fs::create_dir(path)
+            .await
+            .context("create_dir", &[path])?;
         Ok(())
     }
 
     async fn rename_file(&self, path: &str, new_name: &str, overwrite: bool) -> Result<()> {
         let new_path = format!("{}/{new_name}", remove_lowest_path_item(path));
         if !overwrite && self.exists(&new_path).await? {
-            return Err(Error::FileAlreadyExists(new_path));
+            return Err(Error::FileAlreadyExists(new_path.clone())
+                .context("rename_file", &[path, &new_path]));
         }
 
-        tokio::fs::rename(path, new_path).await?;
+        tokio::fs::rename(path, &new_path)
+            .await
+            .context("rename_file", &[path, &new_path])?;
         Ok(())
     }
 
     async fn move_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
         if !overwrite && self.exists(to).await? {
-            return Err(Error::FileAlreadyExists(to.to_string()));
+            return Err(Error::FileAlreadyExists(to.to_string()).context("move_file", &[from, to]));
         }
 
-        tokio::fs::rename(from, to).await?;
+        tokio::fs::rename(from, to)
+            .await
+            .context("move_file", &[from, to])?;
         Ok(())
     }
 
     async fn copy_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
         if !overwrite && self.exists(to).await? {
-            return Err(Error::FileAlreadyExists(to.to_string()));
+            return Err(Error::FileAlreadyExists(to.to_string()).context("copy_file", &[from, to]));
         }
 
-        tokio::fs::copy(from, to).await?;
+        tokio::fs::copy(from, to)
+            .await
+            .context("copy_file", &[from, to])?;
         Ok(())
     }
 
     async fn remove_file(&self, path: &str) -> Result<()> {
-        tokio::fs::remove_file(path).await?;
+        tokio::fs::remove_file(path)
+            .await
+            .context("remove_file", &[path])?;
         Ok(())
     }
 
     async fn remove_dir(&self, path: &str) -> Result<()> {
-        tokio::fs::remove_dir(path).await?;
+        tokio::fs::remove_dir(path)
+            .await
+            .context("remove_dir", &[path])?;
         Ok(())
     }
 
     async fn trash(&self, paths: &[&str]) -> Result<()> {
-        trash::delete_all(paths)?; // FIXME: This is sync...
+        trash::delete_all(paths).context("trash", paths)?; // FIXME: This is sync...
         Ok(())
     }
 
@@ -148,7 +233,9 @@ impl FSBackend for StdBackend {
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+            tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+                .await
+                .context("set_file_permissions_unix", &[path])?;
             Ok(())
         }
 
@@ -158,6 +245,90 @@ impl FSBackend for StdBackend {
             "STD (Not Unix)".into(),
         ))
     }
+
+    async fn set_owner(&self, path: &str, uid: u32, gid: u32) -> Result<()> {
+        #[cfg(unix)]
+        {
+            let owned_path = path.to_string();
+            tokio::task::spawn_blocking(move || {
+                std::os::unix::fs::chown(owned_path, Some(uid), Some(gid))
+            })
+            .await
+            .unwrap_or_else(|error| Err(std::io::Error::other(error)))
+            .context("set_owner", &[path])?;
+            Ok(())
+        }
+
+        #[cfg(not(unix))]
+        Err(Error::Unsupported(
+            "set_owner".into(),
+            "STD (Not Unix)".into(),
+        ))
+    }
+
+    async fn read_link(&self, path: &str) -> Result<String> {
+        link_target(fs::read_link(path).await).context("read_link", &[path])
+    }
+
+    async fn create_symlink(&self, path: &str, target: &str) -> Result<()> {
+        #[cfg(unix)]
+        {
+            fs::symlink(target, path)
+                .await
+                .context("create_symlink", &[path])?;
+            Ok(())
+        }
+
+        // Windows needs to know whether the target is a directory, which it may not be yet
+        #[cfg(not(unix))]
+        Err(Error::Unsupported(
+            "create_symlink".into(),
+            "STD (Not Unix)".into(),
+        ))
+    }
+
+    async fn create_hard_link(&self, path: &str, target: &str) -> Result<()> {
+        fs::hard_link(target, path)
+            .await
+            .context("create_hard_link", &[target, path])?;
+        Ok(())
+    }
+
+    async fn open_write(&self, path: &str, overwrite: bool) -> Result<FileWriter> {
+        if !overwrite && self.exists(path).await? {
+            return Err(Error::FileAlreadyExists(path.to_string()).context("open_write", &[path]));
+        }
+
+        Ok(Box::new(
+            tokio::fs::File::create(path)
+                .await
+                .context("open_write", &[path])?,
+        ))
+    }
+
+    async fn open_read(&self, path: &str) -> Result<FileReader> {
+        Ok(Box::new(
+            tokio::fs::File::open(path)
+                .await
+                .context("open_read", &[path])?,
+        ))
+    }
+
+    async fn read_range(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .context("read_range", &[path])?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .context("read_range", &[path])?;
+
+        let mut contents = vec![];
+        file.take(len)
+            .read_to_end(&mut contents)
+            .await
+            .context("read_range", &[path])?;
+        Ok(contents)
+    }
 }
 
 impl From<StdMetadata> for Metadata {
@@ -175,6 +346,20 @@ impl From<StdMetadata> for Metadata {
         #[cfg(all(not(unix), not(windows)))]
         let size = None;
 
+        #[cfg(unix)]
+        let (link_count, inode, device) = (
+            Some(std_metadata.nlink()),
+            Some(std_metadata.ino()),
+            Some(std_metadata.dev()),
+        );
+        #[cfg(not(unix))]
+        let (link_count, inode, device) = (None, None, None);
+
+        #[cfg(unix)]
+        let (uid, gid) = (Some(std_metadata.uid()), Some(std_metadata.gid()));
+        #[cfg(not(unix))]
+        let (uid, gid) = (None, None);
+
         Metadata {
             r#type: file_type_from_std_metadata(&std_metadata),
             size,
@@ -187,10 +372,36 @@ impl From<StdMetadata> for Metadata {
             } else {
                 None
             },
+            link_target: None,
+            link_count,
+            inode,
+            device,
+            uid,
+            gid,
+            // Looking names up would need the user database, which is left to the caller
+            user: None,
+            group: None,
         }
     }
 }
 
+async fn lstat(path: &str) -> Result<Metadata> {
+    let mut metadata: Metadata = fs::symlink_metadata(path).await?.into();
+    if metadata.r#type == FileType::Symlink {
+        // As in `read_dir`, a link that cannot be read is reported without its target
+        metadata.link_target = link_target(fs::read_link(path).await).ok();
+    }
+
+    Ok(metadata)
+}
+
+fn link_target(result: std::io::Result<PathBuf>) -> Result<String> {
+    result?
+        .into_os_string()
+        .into_string()
+        .map_err(|_| Error::NotUtf8)
+}
+
 fn file_type_from_std_metadata(std_metadata: &StdMetadata) -> FileType {
     #[cfg(unix)]
     use std::os::unix::fs::FileTypeExt;
@@ -0,0 +1,720 @@
+// A battery of scenarios any `FSBackend` is expected to pass identically. Every scenario works
+// inside `root`, which must be an existing, empty directory on the backend, and panics with the
+// name of the failing check so it can be used directly from `#[tokio::test]` functions.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::data::{Capabilities, FileType};
+use crate::error::{ErrorKind, Result};
+use crate::ops::{self, SymlinkPolicy};
+use crate::FSBackend;
+
+pub async fn run_all(backend: &dyn FSBackend, root: &str) {
+    files(backend, &scenario_root(backend, root, "files").await).await;
+    overwrite(backend, &scenario_root(backend, root, "overwrite").await).await;
+    streams(backend, &scenario_root(backend, root, "streams").await).await;
+    if backend.capabilities().contains(Capabilities::APPEND) {
+        append(backend, &scenario_root(backend, root, "append").await).await;
+    }
+    dirs(backend, &scenario_root(backend, root, "dirs").await).await;
+    missing_paths(
+        backend,
+        &scenario_root(backend, root, "missing_paths").await,
+    )
+    .await;
+    relocation(backend, &scenario_root(backend, root, "relocation").await).await;
+    recursive_ops(
+        backend,
+        &scenario_root(backend, root, "recursive_ops").await,
+    )
+    .await;
+    capabilities(backend, &scenario_root(backend, root, "capabilities").await).await;
+    if backend.capabilities().contains(Capabilities::SYMLINKS) {
+        symlinks(backend, &scenario_root(backend, root, "symlinks").await).await;
+    }
+    if backend.capabilities().contains(Capabilities::HARD_LINKS) {
+        hard_links(backend, &scenario_root(backend, root, "hard_links").await).await;
+    }
+}
+
+async fn scenario_root(backend: &dyn FSBackend, root: &str, name: &str) -> String {
+    let scenario_root = format!("{root}/{name}");
+    check(
+        backend.create_dir(&scenario_root).await,
+        "create_dir for the scenario root",
+    );
+    scenario_root
+}
+
+fn check<T>(result: Result<T>, what: &str) -> T {
+    result.unwrap_or_else(|error| panic!("{what} failed: {error:?}"))
+}
+
+pub async fn files(backend: &dyn FSBackend, root: &str) {
+    let path = format!("{root}/File.TXT");
+
+    check(
+        backend
+            .create_file(&path, false, Some(b"hello world"))
+            .await,
+        "create_file",
+    );
+
+    assert!(check(backend.exists(&path).await, "exists"));
+    assert_eq!(
+        check(backend.get_file_type(&path).await, "get_file_type"),
+        FileType::File
+    );
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&path).await,
+            "retrieve_file_content"
+        ),
+        b"hello world"
+    );
+    assert_eq!(
+        check(backend.read_range(&path, 6, 3).await, "read_range"),
+        b"wor"
+    );
+    assert_eq!(
+        check(
+            backend.read_range(&path, 6, 100).await,
+            "read_range past EOF"
+        ),
+        b"world"
+    );
+    assert_eq!(
+        check(
+            backend.read_range(&path, 6, 5).await,
+            "read_range up to EOF"
+        ),
+        b"world"
+    );
+
+    let files = check(
+        backend.retrieve_files(&[&path], true).await,
+        "retrieve_files",
+    );
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, path);
+    assert_eq!(files[0].name, "File.TXT");
+    assert_eq!(files[0].extension.as_deref(), Some("txt"));
+    assert_eq!(files[0].metadata.r#type, FileType::File);
+    assert_eq!(files[0].metadata.size, Some(11));
+
+    check(backend.remove_file(&path).await, "remove_file");
+    assert!(!check(
+        backend.exists(&path).await,
+        "exists after remove_file"
+    ));
+}
+
+pub async fn overwrite(backend: &dyn FSBackend, root: &str) {
+    let path = format!("{root}/file");
+
+    check(
+        backend.create_file(&path, false, Some(b"first")).await,
+        "create_file",
+    );
+
+    let error = backend
+        .create_file(&path, false, Some(b"second"))
+        .await
+        .expect_err("create_file without overwrite must fail on an existing file");
+    assert!(
+        error.is_already_exists_error(),
+        "unexpected error {error:?}"
+    );
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&path).await,
+            "retrieve_file_content"
+        ),
+        b"first"
+    );
+
+    check(
+        backend.create_file(&path, true, Some(b"second")).await,
+        "create_file with overwrite",
+    );
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&path).await,
+            "retrieve_file_content"
+        ),
+        b"second"
+    );
+
+    check(
+        backend.create_file(&path, true, None).await,
+        "create_file with overwrite and no contents",
+    );
+    assert!(check(
+        backend.retrieve_file_content(&path).await,
+        "retrieve_file_content"
+    )
+    .is_empty());
+}
+
+pub async fn streams(backend: &dyn FSBackend, root: &str) {
+    let path = format!("{root}/streamed");
+    let contents: Vec<u8> = (0..=255).cycle().take(256 * 1024).collect();
+
+    let mut writer = check(backend.open_write(&path, false).await, "open_write");
+    writer.write_all(&contents).await.expect("write_all failed");
+    writer.shutdown().await.expect("shutdown failed");
+    drop(writer);
+
+    let error = match backend.open_write(&path, false).await {
+        Ok(_) => panic!("open_write without overwrite must fail on an existing file"),
+        Err(error) => error,
+    };
+    assert!(
+        error.is_already_exists_error(),
+        "unexpected error {error:?}"
+    );
+
+    let mut read_contents = vec![];
+    let mut reader = check(backend.open_read(&path).await, "open_read");
+    reader
+        .read_to_end(&mut read_contents)
+        .await
+        .expect("read_to_end failed");
+    drop(reader);
+    assert!(read_contents == contents, "streamed contents differ");
+
+    let mut writer = check(
+        backend.open_write(&path, true).await,
+        "open_write with overwrite",
+    );
+    writer.write_all(b"short").await.expect("write_all failed");
+    writer.shutdown().await.expect("shutdown failed");
+    drop(writer);
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&path).await,
+            "retrieve_file_content"
+        ),
+        b"short"
+    );
+}
+
+pub async fn append(backend: &dyn FSBackend, root: &str) {
+    let path = format!("{root}/log");
+
+    check(
+        backend.append_file(&path, b"one\n").await,
+        "append_file to a new file",
+    );
+    check(backend.append_file(&path, b"two\n").await, "append_file");
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&path).await,
+            "retrieve_file_content"
+        ),
+        b"one\ntwo\n"
+    );
+}
+
+pub async fn dirs(backend: &dyn FSBackend, root: &str) {
+    let dir = format!("{root}/dir");
+
+    check(backend.create_dir(&dir).await, "create_dir");
+    assert_eq!(
+        check(backend.get_file_type(&dir).await, "get_file_type"),
+        FileType::Dir
+    );
+
+    let error = backend
+        .create_dir(&dir)
+        .await
+        .expect_err("create_dir must fail on an existing directory");
+    assert!(
+        error.is_already_exists_error(),
+        "unexpected error {error:?}"
+    );
+
+    check(
+        backend
+            .create_file(&format!("{dir}/a.rs"), false, Some(b"a"))
+            .await,
+        "create_file",
+    );
+    check(
+        backend.create_dir(&format!("{dir}/sub")).await,
+        "create_dir",
+    );
+
+    let mut files = check(backend.read_dir(&dir).await, "read_dir");
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].name, "a.rs");
+    assert_eq!(files[0].path, format!("{dir}/a.rs"));
+    assert_eq!(files[0].extension.as_deref(), Some("rs"));
+    assert_eq!(files[0].metadata.r#type, FileType::File);
+    assert_eq!(files[0].metadata.size, Some(1));
+    assert_eq!(files[1].name, "sub");
+    assert_eq!(files[1].metadata.r#type, FileType::Dir);
+
+    backend
+        .remove_dir(&dir)
+        .await
+        .expect_err("remove_dir must fail on a non-empty directory");
+
+    check(backend.remove_all(&[&dir]).await, "remove_all");
+    assert!(!check(
+        backend.exists(&dir).await,
+        "exists after remove_all"
+    ));
+}
+
+pub async fn missing_paths(backend: &dyn FSBackend, root: &str) {
+    let path = format!("{root}/missing");
+
+    assert!(!check(backend.exists(&path).await, "exists"));
+
+    for (operation, result) in [
+        ("get_file_type", backend.get_file_type(&path).await.err()),
+        (
+            "retrieve_file_content",
+            backend.retrieve_file_content(&path).await.err(),
+        ),
+        (
+            "retrieve_files",
+            backend.retrieve_files(&[&path], true).await.err(),
+        ),
+        ("read_dir", backend.read_dir(&path).await.err()),
+        ("remove_file", backend.remove_file(&path).await.err()),
+        ("remove_dir", backend.remove_dir(&path).await.err()),
+        (
+            "create_file in a missing directory",
+            backend
+                .create_file(&format!("{path}/file"), false, None)
+                .await
+                .err(),
+        ),
+        (
+            "create_dir in a missing directory",
+            backend.create_dir(&format!("{path}/dir")).await.err(),
+        ),
+    ] {
+        let error = result.unwrap_or_else(|| panic!("{operation} must fail on a missing path"));
+        assert!(
+            error.is_nonexistent_error(),
+            "{operation} returned unexpected error {error:?}"
+        );
+    }
+}
+
+pub async fn relocation(backend: &dyn FSBackend, root: &str) {
+    let a = format!("{root}/a");
+    let b = format!("{root}/b");
+    let c = format!("{root}/c");
+
+    check(
+        backend.create_file(&a, false, Some(b"a")).await,
+        "create_file",
+    );
+    check(
+        backend.create_file(&b, false, Some(b"b")).await,
+        "create_file",
+    );
+
+    check(backend.copy_file(&a, &c, false).await, "copy_file");
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&c).await,
+            "retrieve_file_content"
+        ),
+        b"a"
+    );
+
+    for (operation, result) in [
+        ("copy_file", backend.copy_file(&a, &b, false).await.err()),
+        ("move_file", backend.move_file(&a, &b, false).await.err()),
+        (
+            "rename_file",
+            backend.rename_file(&a, "b", false).await.err(),
+        ),
+    ] {
+        let error = result.unwrap_or_else(|| {
+            panic!("{operation} without overwrite must fail on an existing file")
+        });
+        assert!(
+            error.is_already_exists_error(),
+            "{operation} returned unexpected error {error:?}"
+        );
+    }
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&b).await,
+            "retrieve_file_content"
+        ),
+        b"b"
+    );
+
+    check(
+        backend.move_file(&a, &b, true).await,
+        "move_file with overwrite",
+    );
+    assert!(!check(backend.exists(&a).await, "exists after move_file"));
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&b).await,
+            "retrieve_file_content"
+        ),
+        b"a"
+    );
+
+    check(
+        backend.rename_file(&b, "renamed", false).await,
+        "rename_file",
+    );
+    assert!(!check(backend.exists(&b).await, "exists after rename_file"));
+    assert_eq!(
+        check(
+            backend
+                .retrieve_file_content(&format!("{root}/renamed"))
+                .await,
+            "retrieve_file_content"
+        ),
+        b"a"
+    );
+
+    check(
+        backend
+            .copy_file(&c, &format!("{root}/renamed"), true)
+            .await,
+        "copy_file with overwrite",
+    );
+}
+
+pub async fn recursive_ops(backend: &dyn FSBackend, root: &str) {
+    let src = format!("{root}/src");
+    let dest = format!("{root}/dest");
+
+    check(backend.create_dir(&src).await, "create_dir");
+    check(
+        backend.create_dir(&format!("{src}/nested")).await,
+        "create_dir",
+    );
+    check(backend.create_dir(&dest).await, "create_dir");
+    check(
+        backend
+            .create_file(&format!("{src}/one"), false, Some(b"1"))
+            .await,
+        "create_file",
+    );
+    check(
+        backend
+            .create_file(&format!("{src}/nested/two"), false, Some(b"22"))
+            .await,
+        "create_file",
+    );
+
+    assert_eq!(
+        check(
+            backend.calculate_total_size(&[&src]).await,
+            "calculate_total_size"
+        ),
+        3
+    );
+
+    check(
+        ops::copy_files(
+            backend,
+            std::slice::from_ref(&src),
+            dest.clone(),
+            SymlinkPolicy::Preserve,
+        )
+        .await,
+        "copy_files",
+    );
+    assert_eq!(
+        check(
+            backend
+                .retrieve_file_content(&format!("{dest}/src/nested/two"))
+                .await,
+            "retrieve_file_content"
+        ),
+        b"22"
+    );
+
+    check(
+        backend.remove_all(&[&format!("{dest}/src")]).await,
+        "remove_all",
+    );
+    check(
+        ops::move_files(
+            backend,
+            std::slice::from_ref(&src),
+            dest.clone(),
+            SymlinkPolicy::Preserve,
+        )
+        .await,
+        "move_files",
+    );
+    assert!(!check(
+        backend.exists(&src).await,
+        "exists after move_files"
+    ));
+    assert_eq!(
+        check(
+            backend
+                .retrieve_file_content(&format!("{dest}/src/one"))
+                .await,
+            "retrieve_file_content"
+        ),
+        b"1"
+    );
+}
+
+// Only checks what can be verified without side effects outside of `root`
+pub async fn capabilities(backend: &dyn FSBackend, root: &str) {
+    let capabilities = backend.capabilities();
+    let path = format!("{root}/file");
+
+    check(
+        backend.create_file(&path, false, Some(b"file")).await,
+        "create_file",
+    );
+
+    if !capabilities.contains(Capabilities::TRASH) {
+        let error = backend
+            .trash(&[&path])
+            .await
+            .expect_err("trash must fail without the TRASH capability");
+        assert_eq!(
+            error.kind(),
+            ErrorKind::Unsupported,
+            "unexpected error {error:?}"
+        );
+    }
+
+    let result = backend.set_file_permissions_unix(&path, 0o600).await;
+    if capabilities.contains(Capabilities::SET_PERMISSIONS) {
+        check(result, "set_file_permissions_unix");
+    } else {
+        let error =
+            result.expect_err("set_file_permissions_unix must fail without SET_PERMISSIONS");
+        assert_eq!(
+            error.kind(),
+            ErrorKind::Unsupported,
+            "unexpected error {error:?}"
+        );
+    }
+
+    if !capabilities.contains(Capabilities::SYMLINKS) {
+        let error = backend
+            .create_symlink(&format!("{root}/link"), &path)
+            .await
+            .expect_err("create_symlink must fail without the SYMLINKS capability");
+        assert_eq!(
+            error.kind(),
+            ErrorKind::Unsupported,
+            "unexpected error {error:?}"
+        );
+    }
+
+    if !capabilities.contains(Capabilities::APPEND) {
+        let error = backend
+            .append_file(&path, b"!")
+            .await
+            .expect_err("append_file must fail without the APPEND capability");
+        assert_eq!(
+            error.kind(),
+            ErrorKind::Unsupported,
+            "unexpected error {error:?}"
+        );
+    }
+
+    if !capabilities.contains(Capabilities::HARD_LINKS) {
+        let error = backend
+            .create_hard_link(&format!("{root}/hard_link"), &path)
+            .await
+            .expect_err("create_hard_link must fail without the HARD_LINKS capability");
+        assert_eq!(
+            error.kind(),
+            ErrorKind::Unsupported,
+            "unexpected error {error:?}"
+        );
+    }
+
+    // Handing a file to its current owner is allowed without privileges
+    let owner = &check(
+        backend.retrieve_files(&[&path], true).await,
+        "retrieve_files",
+    )[0]
+    .metadata;
+    if capabilities.contains(Capabilities::SET_OWNER) {
+        if let (Some(uid), Some(gid)) = (owner.uid, owner.gid) {
+            check(backend.set_owner(&path, uid, gid).await, "set_owner");
+
+            let owner = &check(
+                backend.retrieve_files(&[&path], true).await,
+                "retrieve_files",
+            )[0]
+            .metadata;
+            assert_eq!((owner.uid, owner.gid), (Some(uid), Some(gid)));
+        }
+    } else {
+        let error = backend
+            .set_owner(&path, owner.uid.unwrap_or(0), owner.gid.unwrap_or(0))
+            .await
+            .expect_err("set_owner must fail without the SET_OWNER capability");
+        assert_eq!(
+            error.kind(),
+            ErrorKind::Unsupported,
+            "unexpected error {error:?}"
+        );
+    }
+
+    let metadata = &check(
+        backend.retrieve_files(&[&path], true).await,
+        "retrieve_files",
+    )[0]
+    .metadata;
+    assert_eq!(
+        metadata.modified.is_some(),
+        capabilities.contains(Capabilities::MODIFIED_TIME),
+        "modification time does not match the MODIFIED_TIME capability"
+    );
+}
+
+pub async fn symlinks(backend: &dyn FSBackend, root: &str) {
+    let target = format!("{root}/target");
+    let link = format!("{root}/link");
+
+    check(
+        backend.create_file(&target, false, Some(b"target")).await,
+        "create_file",
+    );
+    // Relative targets resolve the same way on every backend
+    check(
+        backend.create_symlink(&link, "target").await,
+        "create_symlink",
+    );
+
+    assert_eq!(check(backend.read_link(&link).await, "read_link"), "target");
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&link).await,
+            "retrieve_file_content through a symlink"
+        ),
+        b"target"
+    );
+
+    let files = check(backend.read_dir(root).await, "read_dir");
+    let listed_link = files
+        .iter()
+        .find(|file| file.name == "link")
+        .expect("read_dir must list the symlink");
+    assert_eq!(listed_link.metadata.r#type, FileType::Symlink);
+    assert_eq!(listed_link.metadata.link_target.as_deref(), Some("target"));
+
+    let metadata = check(backend.symlink_metadata(&link).await, "symlink_metadata");
+    assert_eq!(metadata.r#type, FileType::Symlink);
+    assert_eq!(metadata.link_target.as_deref(), Some("target"));
+    for (follow_symlinks, r#type) in [(true, FileType::File), (false, FileType::Symlink)] {
+        let files = check(
+            backend.retrieve_files(&[&link], follow_symlinks).await,
+            "retrieve_files",
+        );
+        assert_eq!(
+            files[0].metadata.r#type, r#type,
+            "retrieve_files with follow_symlinks {follow_symlinks}"
+        );
+    }
+
+    // Files created through a symlinked directory end up in the directory itself
+    let dir = format!("{root}/dir");
+    let dir_link = format!("{root}/dir_link");
+    check(backend.create_dir(&dir).await, "create_dir");
+    check(
+        backend.create_symlink(&dir_link, "dir").await,
+        "create_symlink to a directory",
+    );
+    check(
+        backend
+            .create_file(&format!("{dir_link}/child"), false, Some(b"child"))
+            .await,
+        "create_file through a symlinked directory",
+    );
+    assert_eq!(
+        check(
+            backend.get_file_type(&format!("{dir_link}/child")).await,
+            "get_file_type through a symlinked directory"
+        ),
+        FileType::File
+    );
+    assert_eq!(
+        check(
+            backend.symlink_metadata(&format!("{dir_link}/child")).await,
+            "symlink_metadata through a symlinked directory"
+        )
+        .r#type,
+        FileType::File
+    );
+    let files = check(backend.read_dir(&dir).await, "read_dir");
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].name, "child");
+
+    let error = backend
+        .create_symlink(&link, "target")
+        .await
+        .expect_err("create_symlink must fail on an existing file");
+    assert!(
+        error.is_already_exists_error(),
+        "unexpected error {error:?}"
+    );
+}
+
+pub async fn hard_links(backend: &dyn FSBackend, root: &str) {
+    let target = format!("{root}/target");
+    let link = format!("{root}/link");
+
+    check(
+        backend.create_file(&target, false, Some(b"target")).await,
+        "create_file",
+    );
+    check(
+        backend.create_hard_link(&link, &target).await,
+        "create_hard_link",
+    );
+
+    // Both names refer to the same contents, so changes through one are visible through the other
+    check(backend.append_file(&link, b"!").await, "append_file");
+    assert_eq!(
+        check(
+            backend.retrieve_file_content(&target).await,
+            "retrieve_file_content"
+        ),
+        b"target!"
+    );
+
+    let files = check(
+        backend.retrieve_files(&[&target, &link], false).await,
+        "retrieve_files",
+    );
+    assert_eq!(files[1].metadata.r#type, FileType::File);
+    for file in &files {
+        if let Some(link_count) = file.metadata.link_count {
+            assert_eq!(link_count, 2, "link count of {}", file.path);
+        }
+    }
+    if let (Some(target_inode), Some(link_inode)) =
+        (files[0].metadata.inode, files[1].metadata.inode)
+    {
+        assert_eq!(target_inode, link_inode);
+        assert_eq!(files[0].metadata.device, files[1].metadata.device);
+    }
+
+    let error = backend
+        .create_hard_link(&link, &target)
+        .await
+        .expect_err("create_hard_link must fail on an existing file");
+    assert!(
+        error.is_already_exists_error(),
+        "unexpected error {error:?}"
+    );
+}
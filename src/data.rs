@@ -24,10 +24,21 @@ pub struct Metadata {
     pub size: Option<u64>,
     pub readonly: bool,
     pub unix_mode: Option<Mode>,
+    // Only known for symlinks whose metadata was retrieved without following them
+    pub link_target: Option<String>,
+    // Files with the same inode on the same device are hard links to each other
+    pub link_count: Option<u64>,
+    pub inode: Option<u64>,
+    pub device: Option<u64>,
+    // Names are only known where the protocol sends them along with the ids
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub user: Option<String>,
+    pub group: Option<String>,
 }
 
 #[non_exhaustive]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FileType {
     File,
@@ -58,3 +69,25 @@ impl FileType {
         }
     }
 }
+
+bitflags::bitflags! {
+    // What a backend supports, operations outside of it fail with an unsupported error
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct Capabilities: u32 {
+        const TRASH = 1 << 0;
+        const SET_PERMISSIONS = 1 << 1;
+        const SYMLINKS = 1 << 2;
+        // Copies happen on the backend's side instead of passing the contents through the client
+        const SERVER_SIDE_COPY = 1 << 3;
+        const ATOMIC_RENAME = 1 << 4;
+        // Ranges are read without transferring the preceding contents
+        const RANGED_READS = 1 << 5;
+        const APPEND = 1 << 6;
+        const MODIFIED_TIME = 1 << 7;
+        const ACCESSED_TIME = 1 << 8;
+        const CREATED_TIME = 1 << 9;
+        const HARD_LINKS = 1 << 10;
+        const SET_OWNER = 1 << 11;
+    }
+}
@@ -1,21 +1,38 @@
 use std::sync::Arc;
 
+use russh::AgentAuthError;
+use russh_sftp::client::error::Error as SFTPError;
+use russh_sftp::protocol::StatusCode;
+use suppaftp::types::Response;
+use suppaftp::{FtpError, Status};
+
 use crate::data::FileType;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum Error {
-    #[error("Rust StdIO Error (${0})")]
-    StdIO(std::io::ErrorKind),
+    #[error("Rust StdIO Error ({0})")]
+    StdIO(#[source] Arc<std::io::Error>),
     #[error("FTP Error ({0})")]
-    FTP(Arc<suppaftp::types::FtpError>),
+    FTP(#[source] Arc<suppaftp::types::FtpError>),
     #[error("SFTP Error ({0})")]
     SFTP(#[from] russh_sftp::client::error::Error),
+    #[error("SSH Error ({0})")]
+    SSH(#[source] Arc<russh::Error>),
+    #[error("SSH Key Error ({0})")]
+    SSHKey(#[source] Arc<russh::keys::Error>),
     #[error("Trash Error ({0})")]
-    Trash(Arc<trash::Error>),
+    Trash(#[source] Arc<trash::Error>),
+    // Names the operation and the path(s) it failed on, wrapping the error that caused it
+    #[error("{operation} {}: {source}", quote_paths(paths))]
+    Context {
+        operation: &'static str,
+        paths: Vec<String>,
+        source: Box<Error>,
+    },
 
-    #[error("Cannot copy or move file of type")] // TODO
+    #[error("Cannot copy or move a file of type {0:?}")]
     CannotCopyOrMoveFileType(FileType),
     #[error("File at path '{0}' is nonexistent")]
     FileNonexistent(String),
@@ -25,22 +42,248 @@ pub enum Error {
     NoFileName,
     #[error("Failed to convert string to UTF-8")]
     NotUtf8,
+    #[error("Host key of '{0}' differs from the recorded one")]
+    HostKeyMismatch(String),
+    #[error("Host key of '{0}' is not trusted")]
+    HostKeyRejected(String),
+    #[error("Server rejected authentication as user '{0}'")]
+    AuthenticationFailed(String),
+    #[error("Could not parse directory listing entry '{0}'")]
+    UnparseableDirEntry(String),
+    #[error("Following the symlink '{0}' leads back into a directory it is in")]
+    SymlinkLoop(String),
     #[error("Operation '{0}' is unsupported on platform '{1}'")]
     Unsupported(String, String),
 }
 
+// A backend independent classification of what went wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    ConnectionLost,
+    Timeout,
+    AuthenticationFailed,
+    HostKeyRejected,
+    Unsupported,
+    InvalidData,
+    Other,
+}
+
 impl Error {
+    // Attaches the operation and path(s) an error occurred on, unless the error already names
+    // the same operation
+    pub(crate) fn context(self, operation: &'static str, paths: &[&str]) -> Self {
+        match self {
+            Self::Context {
+                operation: existing,
+                ..
+            } if existing == operation => self,
+            error => Self::Context {
+                operation,
+                paths: paths.iter().map(|path| path.to_string()).collect(),
+                source: Box::new(error),
+            },
+        }
+    }
+
+    // The outermost operation that failed, if it is known
+    pub fn operation(&self) -> Option<&'static str> {
+        match self {
+            Self::Context { operation, .. } => Some(operation),
+            _ => None,
+        }
+    }
+
+    pub fn paths(&self) -> &[String] {
+        match self {
+            Self::Context { paths, .. } => paths,
+            _ => &[],
+        }
+    }
+
+    // The error without any operation and path context, which is what to match variants against
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.root_cause(),
+            error => error,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::StdIO(error) => io_error_kind(error.kind()),
+            Self::FTP(error) => match error.as_ref() {
+                FtpError::ConnectionError(error) => match error.kind() {
+                    std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+                    _ => ErrorKind::ConnectionLost,
+                },
+                FtpError::UnexpectedResponse(response) => ftp_reply_kind(response),
+                FtpError::SecureError(_) => ErrorKind::ConnectionLost,
+                FtpError::BadResponse => ErrorKind::InvalidData,
+                FtpError::InvalidAddress(_) => ErrorKind::Other,
+            },
+            Self::SFTP(error) => match error {
+                SFTPError::Status(status) => match status.status_code {
+                    StatusCode::NoSuchFile => ErrorKind::NotFound,
+                    StatusCode::PermissionDenied => ErrorKind::PermissionDenied,
+                    StatusCode::NoConnection | StatusCode::ConnectionLost => {
+                        ErrorKind::ConnectionLost
+                    }
+                    StatusCode::OpUnsupported => ErrorKind::Unsupported,
+                    StatusCode::BadMessage => ErrorKind::InvalidData,
+                    _ => ErrorKind::Other,
+                },
+                SFTPError::IO(_) => ErrorKind::ConnectionLost,
+                SFTPError::Timeout => ErrorKind::Timeout,
+                // What requests fail with once the channel of the session has been closed
+                SFTPError::UnexpectedBehavior(message)
+                    if message == "session closed"
+                        || message == "sender dropped"
+                        || message.starts_with("SendError") =>
+                {
+                    ErrorKind::ConnectionLost
+                }
+                SFTPError::UnexpectedPacket | SFTPError::UnexpectedBehavior(_) => {
+                    ErrorKind::InvalidData
+                }
+                SFTPError::Limited(_) => ErrorKind::Other,
+            },
+            Self::SSH(error) => match error.as_ref() {
+                russh::Error::Disconnect
+                | russh::Error::HUP
+                | russh::Error::SendError
+                | russh::Error::RecvError => ErrorKind::ConnectionLost,
+                russh::Error::IO(error) => network_io_error_kind(error.kind()),
+                russh::Error::ConnectionTimeout
+                | russh::Error::KeepaliveTimeout
+                | russh::Error::InactivityTimeout
+                | russh::Error::Elapsed(_) => ErrorKind::Timeout,
+                russh::Error::NotAuthenticated | russh::Error::NoAuthMethod => {
+                    ErrorKind::AuthenticationFailed
+                }
+                russh::Error::UnknownKey
+                | russh::Error::WrongServerSig
+                | russh::Error::KeyChanged { .. } => ErrorKind::HostKeyRejected,
+                _ => ErrorKind::Other,
+            },
+            Self::SSHKey(_) | Self::Trash(_) | Self::SymlinkLoop(_) => ErrorKind::Other,
+            Self::Context { source, .. } => source.kind(),
+
+            Self::CannotCopyOrMoveFileType(_) | Self::Unsupported(_, _) => ErrorKind::Unsupported,
+            Self::FileNonexistent(_) => ErrorKind::NotFound,
+            Self::FileAlreadyExists(_) => ErrorKind::AlreadyExists,
+            Self::NoFileName | Self::NotUtf8 | Self::UnparseableDirEntry(_) => {
+                ErrorKind::InvalidData
+            }
+            Self::HostKeyMismatch(_) | Self::HostKeyRejected(_) => ErrorKind::HostKeyRejected,
+            Self::AuthenticationFailed(_) => ErrorKind::AuthenticationFailed,
+        }
+    }
+
     pub fn is_already_exists_error(&self) -> bool {
-        matches!(
-            self,
-            Self::FileAlreadyExists(_) | Self::StdIO(std::io::ErrorKind::AlreadyExists)
-        )
+        self.kind() == ErrorKind::AlreadyExists
+    }
+
+    pub fn is_nonexistent_error(&self) -> bool {
+        self.kind() == ErrorKind::NotFound
+    }
+
+    // Whether the same operation may succeed if attempted again, e.g. after a dropped connection
+    pub fn is_transient_error(&self) -> bool {
+        match self.root_cause() {
+            Self::StdIO(error) if error.kind() == std::io::ErrorKind::Interrupted => true,
+            // 4xx replies are transient negative completions by definition
+            Self::FTP(error) => match error.as_ref() {
+                FtpError::UnexpectedResponse(response) => {
+                    (400..500).contains(&response.status.code())
+                        && self.kind() != ErrorKind::AuthenticationFailed
+                }
+                _ => matches!(self.kind(), ErrorKind::ConnectionLost | ErrorKind::Timeout),
+            },
+            _ => matches!(self.kind(), ErrorKind::ConnectionLost | ErrorKind::Timeout),
+        }
+    }
+}
+
+// Running out of data early only means the other side went away on a connection, a local file may
+// simply be shorter than expected
+pub(crate) fn network_io_error_kind(kind: std::io::ErrorKind) -> ErrorKind {
+    match kind {
+        std::io::ErrorKind::UnexpectedEof => ErrorKind::ConnectionLost,
+        kind => io_error_kind(kind),
+    }
+}
+
+fn io_error_kind(kind: std::io::ErrorKind) -> ErrorKind {
+    use std::io::ErrorKind as IOErrorKind;
+
+    match kind {
+        IOErrorKind::NotFound => ErrorKind::NotFound,
+        IOErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
+        IOErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+        IOErrorKind::NotADirectory => ErrorKind::NotADirectory,
+        IOErrorKind::IsADirectory => ErrorKind::IsADirectory,
+        IOErrorKind::DirectoryNotEmpty => ErrorKind::DirectoryNotEmpty,
+        IOErrorKind::ConnectionRefused
+        | IOErrorKind::ConnectionReset
+        | IOErrorKind::ConnectionAborted
+        | IOErrorKind::NotConnected
+        | IOErrorKind::BrokenPipe => ErrorKind::ConnectionLost,
+        IOErrorKind::TimedOut => ErrorKind::Timeout,
+        IOErrorKind::Unsupported => ErrorKind::Unsupported,
+        IOErrorKind::InvalidData => ErrorKind::InvalidData,
+        _ => ErrorKind::Other,
+    }
+}
+
+// Only the reply code is looked at, the text varies from server to server. 550 covers every failed
+// file action and stays unclassified, the FTP backend asks the server about missing files itself.
+fn ftp_reply_kind(response: &Response) -> ErrorKind {
+    match response.status {
+        Status::NotAvailable | Status::CannotOpenDataConnection | Status::TransferAborted => {
+            ErrorKind::ConnectionLost
+        }
+        Status::NotLoggedIn | Status::InvalidCredentials => ErrorKind::AuthenticationFailed,
+        Status::BadCommand | Status::NotImplemented | Status::NotImplementedParameter => {
+            ErrorKind::Unsupported
+        }
+        Status::BadFilename => ErrorKind::InvalidData,
+        _ => ErrorKind::Other,
+    }
+}
+
+fn quote_paths(paths: &[String]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("'{path}'"))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+pub(crate) trait ResultExt<T> {
+    fn context(self, operation: &'static str, paths: &[&str]) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn context(self, operation: &'static str, paths: &[&str]) -> Result<T> {
+        self.map_err(|error| error.into().context(operation, paths))
     }
 }
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        Self::StdIO(value.kind())
+        Self::StdIO(Arc::new(value))
+    }
+}
+
+impl From<std::io::ErrorKind> for Error {
+    fn from(value: std::io::ErrorKind) -> Self {
+        std::io::Error::from(value).into()
     }
 }
 
@@ -50,6 +293,27 @@ impl From<suppaftp::types::FtpError> for Error {
     }
 }
 
+impl From<russh::Error> for Error {
+    fn from(value: russh::Error) -> Self {
+        Self::SSH(Arc::new(value))
+    }
+}
+
+impl From<russh::keys::Error> for Error {
+    fn from(value: russh::keys::Error) -> Self {
+        Self::SSHKey(Arc::new(value))
+    }
+}
+
+impl From<AgentAuthError> for Error {
+    fn from(value: AgentAuthError) -> Self {
+        match value {
+            AgentAuthError::Send(_) => russh::Error::SendError.into(),
+            AgentAuthError::Key(error) => error.into(),
+        }
+    }
+}
+
 impl From<trash::Error> for Error {
     fn from(value: trash::Error) -> Self {
         Self::Trash(Arc::new(value))
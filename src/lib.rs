@@ -1,24 +1,55 @@
 pub mod backends;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
 pub mod data;
 pub mod error;
+#[cfg(test)]
+mod fixtures;
 pub mod ops;
 mod util;
 
+use std::future::Future;
+use std::io::{self, Cursor};
+use std::mem;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
 use async_trait::async_trait;
-use data::{File, FileType};
+use data::{Capabilities, File, FileType, Metadata};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+use crate::error::{Error, Result, ResultExt};
 
-use crate::error::Result;
+pub type FileReader = Box<dyn AsyncRead + Send + Unpin>;
+// Writers may borrow the backend they were opened from
+pub type FileWriter<'a> = Box<dyn AsyncWrite + Send + Unpin + 'a>;
 
 #[async_trait]
 pub trait FSBackend: Send + Sync {
+    // Backends advertise what they support, nothing is assumed otherwise
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::empty()
+    }
     async fn disconnect(&self) -> Result<()>;
     async fn exists(&self, path: &str) -> Result<bool>;
     async fn get_file_type(&self, path: &str) -> Result<FileType>;
-    async fn retrieve_files(&self, paths: &[&str]) -> Result<Vec<File>>;
+    // Without following symlinks, the metadata is that of the links themselves
+    async fn retrieve_files(&self, paths: &[&str], follow_symlinks: bool) -> Result<Vec<File>>;
+    async fn symlink_metadata(&self, path: &str) -> Result<Metadata> {
+        self.retrieve_files(&[path], false)
+            .await?
+            .pop()
+            .map(|file| file.metadata)
+            .ok_or_else(|| Error::FileNonexistent(path.to_string()))
+            .context("symlink_metadata", &[path])
+    }
     async fn retrieve_file_content(&self, path: &str) -> Result<Vec<u8>>;
     async fn read_dir(&self, path: &str) -> Result<Vec<File>>;
     async fn create_file(&self, path: &str, overwrite: bool, contents: Option<&[u8]>)
         -> Result<()>;
+    async fn append_file(&self, _path: &str, _contents: &[u8]) -> Result<()> {
+        Err(unsupported::<Self>("append_file"))
+    }
     async fn create_dir(&self, path: &str) -> Result<()>;
     async fn rename_file(&self, path: &str, new_name: &str, overwrite: bool) -> Result<()>;
     async fn move_file(&self, from: &str, to: &str, overwrite: bool) -> Result<()>;
@@ -27,13 +58,66 @@ pub trait FSBackend: Send + Sync {
     async fn remove_dir(&self, path: &str) -> Result<()>;
     async fn trash(&self, paths: &[&str]) -> Result<()>;
     async fn set_file_permissions_unix(&self, path: &str, mode: u32) -> Result<()>;
+    async fn set_owner(&self, _path: &str, _uid: u32, _gid: u32) -> Result<()> {
+        Err(unsupported::<Self>("set_owner"))
+    }
+    async fn read_link(&self, _path: &str) -> Result<String> {
+        Err(unsupported::<Self>("read_link"))
+    }
+    // Creates a symlink at `path` pointing to `target`, which is stored as given
+    async fn create_symlink(&self, _path: &str, _target: &str) -> Result<()> {
+        Err(unsupported::<Self>("create_symlink"))
+    }
+    // Creates `path` as another name for the existing file `target`
+    async fn create_hard_link(&self, _path: &str, _target: &str) -> Result<()> {
+        Err(unsupported::<Self>("create_hard_link"))
+    }
+
+    // The written file is only guaranteed to be complete once the writer has been shut down.
+    // The default implementation buffers the whole file until then, backends should override it
+    async fn open_write(&self, path: &str, overwrite: bool) -> Result<FileWriter> {
+        if !overwrite && self.exists(path).await? {
+            return Err(Error::FileAlreadyExists(path.to_string()).context("open_write", &[path]));
+        }
+
+        Ok(Box::new(BufferedWriter {
+            backend: self,
+            path: path.to_string(),
+            overwrite,
+            contents: vec![],
+            create: None,
+            created: false,
+        }))
+    }
+
+    // The default implementation buffers the whole file, backends should override it
+    async fn open_read(&self, path: &str) -> Result<FileReader> {
+        Ok(Box::new(Cursor::new(
+            self.retrieve_file_content(path).await?,
+        )))
+    }
+
+    async fn read_range(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut reader = self.open_read(path).await?;
+        tokio::io::copy(&mut (&mut reader).take(offset), &mut tokio::io::sink())
+            .await
+            .context("read_range", &[path])?;
+
+        let mut contents = vec![];
+        reader
+            .take(len)
+            .read_to_end(&mut contents)
+            .await
+            .context("read_range", &[path])?;
+        Ok(contents)
+    }
 
     async fn calculate_total_size(&self, paths: &[&str]) -> Result<u64> {
         let mut total_size = 0;
 
         let mut dirs_to_process = vec![];
 
-        for file in self.retrieve_files(paths).await? {
+        for file in self.retrieve_files(paths, true).await? {
             if file.metadata.r#type == FileType::Dir {
                 dirs_to_process.push(file.path);
             } else {
@@ -101,28 +185,730 @@ pub trait FSBackend: Send + Sync {
     }
 }
 
+// Default implementations of optional operations fail with this, naming the backend by its type
+fn unsupported<B: ?Sized>(operation: &str) -> Error {
+    let backend = std::any::type_name::<B>()
+        .rsplit("::")
+        .next()
+        .unwrap_or_default();
+    Error::Unsupported(operation.into(), backend.into())
+}
+
+// Hands everything written to `create_file` once shut down
+struct BufferedWriter<'a, B: FSBackend + ?Sized> {
+    backend: &'a B,
+    path: String,
+    overwrite: bool,
+    contents: Vec<u8>,
+    create: Option<Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>>,
+    created: bool,
+}
+
+impl<B: FSBackend + ?Sized> AsyncWrite for BufferedWriter<'_, B> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.create.is_some() || this.created {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        this.contents.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.created {
+            return Poll::Ready(Ok(()));
+        }
+
+        let create = this.create.get_or_insert_with(|| {
+            let (backend, path, overwrite, contents) = (
+                this.backend,
+                mem::take(&mut this.path),
+                this.overwrite,
+                mem::take(&mut this.contents),
+            );
+            Box::pin(async move { backend.create_file(&path, overwrite, Some(&contents)).await })
+        });
+        let result = ready!(create.as_mut().poll(cx));
+
+        this.create = None;
+        this.created = true;
+        Poll::Ready(result.map_err(io::Error::other))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::backends::ftp::FTPBackend;
-    use suppaftp::AsyncNativeTlsFtpStream;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use russh::keys::PublicKey;
+    use tokio::io::AsyncWriteExt;
+
+    use crate::backends::ftp::{FTPBackend, FTPSecurity};
+    use crate::backends::memory::MemoryBackend;
+    use crate::backends::reconnect::ReconnectPolicy;
+    use crate::backends::retry::{RetryBackend, RetryPolicy};
+    use crate::backends::sftp::{HostKeyPolicy, SFTPBackend};
+    use crate::backends::std::StdBackend;
+    use crate::data::{Capabilities, FileType};
+    use crate::error::{Error, ErrorKind, Result};
+    use crate::fixtures::{
+        account_name, FTPServer, SFTPServer, SFTP_CLIENT_KEY, SFTP_CLIENT_KEY_PASSPHRASE,
+        SFTP_CLIENT_PUBLIC_KEY, SFTP_PASSWORD, SFTP_USER,
+    };
+    use crate::ops::{SymlinkPolicy, TransitProgressResponse};
+    use crate::{conformance, ops, FSBackend};
 
     #[tokio::test]
     async fn ftp() {
-        let mut ftp_stream = AsyncNativeTlsFtpStream::connect("ftp.dlptest.com:21")
-            .await
-            .expect("Failed to connect to FTP test server");
+        let server = FTPServer::start().await;
+        let backend = server.connect().await;
 
-        ftp_stream
-            .login("dlpuser", "rNrKYTX9g7z3RgJRmxWuGHbeu")
+        backend
+            .create_file("/hello.txt", false, Some(b"hello"))
             .await
-            .expect("Failed to login to FTP server");
+            .unwrap();
+        assert_eq!(
+            std::fs::read(server.root.path().join("hello.txt")).unwrap(),
+            b"hello"
+        );
+
+        // A writer dropped halfway must not leave the partial upload behind as if it succeeded
+        let mut writer = backend.open_write("/partial.txt", false).await.unwrap();
+        writer.write_all(b"part").await.unwrap();
+        drop(writer);
+        assert!(!backend.exists("/partial.txt").await.unwrap());
 
-        let mut backend = FTPBackend::new(ftp_stream);
+        // A single connection cannot read and write at once, copies within it must not wait on it
+        backend.create_dir("/copies").await.unwrap();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            ops::copy_files_between(
+                &backend,
+                &backend,
+                &["/hello.txt"],
+                "/copies",
+                SymlinkPolicy::Error,
+            ),
+        )
+        .await
+        .expect("Copying within a single FTP connection deadlocked")
+        .unwrap();
+        assert_eq!(
+            backend
+                .retrieve_file_content("/copies/hello.txt")
+                .await
+                .unwrap(),
+            b"hello"
+        );
 
         backend
             .unwrap()
             .quit()
             .await
             .expect("Failed to gracefully disconnect from FTP server");
+
+        // The fixture does not offer TLS, so upgrading the connection must fail cleanly
+        assert!(FTPBackend::connect("127.0.0.1")
+            .port(server.addr.port())
+            .security(FTPSecurity::ExplicitTls)
+            .build()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn ftp_conformance() {
+        let server = FTPServer::start().await;
+        let backend = server.connect().await;
+
+        conformance::run_all(&backend, "").await;
+        backend.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sftp() {
+        let server = SFTPServer::start().await;
+        let backend = server.connect().await;
+
+        backend
+            .create_file("/hello.txt", false, Some(b"hello"))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(server.root.path().join("hello.txt")).unwrap(),
+            b"hello"
+        );
+
+        // Owner names are taken from the longnames of a directory listing
+        let owner = &backend.read_dir("/").await.unwrap()[0].metadata;
+        assert_eq!(owner.user, account_name("/etc/passwd", owner.uid.unwrap()));
+        assert_eq!(owner.group, account_name("/etc/group", owner.gid.unwrap()));
+
+        // The fixture offers hardlink@openssh.com, which is what hard links are advertised on
+        assert!(backend.capabilities().contains(Capabilities::HARD_LINKS));
+
+        backend.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sftp_authentication() {
+        let server = SFTPServer::start().await;
+        let key_dir = tempfile::tempdir().unwrap();
+        let key_path = key_dir.path().join("id_ed25519");
+        std::fs::write(&key_path, SFTP_CLIENT_KEY).unwrap();
+
+        let backend = SFTPBackend::connect("127.0.0.1", SFTP_USER)
+            .port(server.addr.port())
+            .private_key(&key_path, Some(SFTP_CLIENT_KEY_PASSPHRASE))
+            .host_key_policy(server.host_key_policy())
+            .build()
+            .await
+            .unwrap();
+        assert!(backend.exists("/").await.unwrap());
+        backend.disconnect().await.unwrap();
+
+        let error = SFTPBackend::connect("127.0.0.1", SFTP_USER)
+            .port(server.addr.port())
+            .password("wrong")
+            .host_key_policy(server.host_key_policy())
+            .build()
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::AuthenticationFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn sftp_host_key_verification() {
+        let server = SFTPServer::start().await;
+        let known_hosts_dir = tempfile::tempdir().unwrap();
+        let known_hosts = known_hosts_dir.path().join("known_hosts");
+        let connect = |policy| {
+            SFTPBackend::connect("127.0.0.1", SFTP_USER)
+                .port(server.addr.port())
+                .password(SFTP_PASSWORD)
+                .host_key_policy(policy)
+                .build()
+        };
+
+        assert!(matches!(
+            connect(HostKeyPolicy::KnownHosts(Some(known_hosts.clone()))).await,
+            Err(Error::HostKeyRejected(_))
+        ));
+        assert!(matches!(
+            connect(HostKeyPolicy::Custom(Arc::new(|_, _, _| false))).await,
+            Err(Error::HostKeyRejected(_))
+        ));
+
+        // The first connection records the key, which strict checking then accepts
+        connect(HostKeyPolicy::TrustOnFirstUse(Some(known_hosts.clone())))
+            .await
+            .unwrap();
+        connect(HostKeyPolicy::KnownHosts(Some(known_hosts.clone())))
+            .await
+            .unwrap();
+
+        let other_key = PublicKey::from_openssh(SFTP_CLIENT_PUBLIC_KEY).unwrap();
+        std::fs::write(
+            &known_hosts,
+            format!(
+                "[127.0.0.1]:{} {}\n",
+                server.addr.port(),
+                other_key.to_openssh().unwrap()
+            ),
+        )
+        .unwrap();
+        for policy in [
+            HostKeyPolicy::KnownHosts(Some(known_hosts.clone())),
+            HostKeyPolicy::TrustOnFirstUse(Some(known_hosts.clone())),
+        ] {
+            assert!(matches!(
+                connect(policy).await,
+                Err(Error::HostKeyMismatch(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn sftp_conformance() {
+        let server = SFTPServer::start().await;
+        let backend = server.connect().await;
+
+        conformance::run_all(&backend, "").await;
+        backend.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sftp_transfers() {
+        let server = SFTPServer::start().await;
+        let backend = server.connect().await;
+        let local_root = tempfile::tempdir().unwrap();
+        let local = local_root.path().to_str().unwrap();
+
+        std::fs::create_dir_all(server.root.path().join("src/nested")).unwrap();
+        std::fs::write(server.root.path().join("src/one"), b"1").unwrap();
+        std::fs::write(server.root.path().join("src/nested/two"), b"22").unwrap();
+        for dir in ["copied", "moved", "copied_progress", "moved_progress"] {
+            backend.create_dir(&format!("/{dir}")).await.unwrap();
+        }
+
+        let continue_on_progress = |_| async { TransitProgressResponse::ContinueOrAbort };
+
+        ops::copy_files(&backend, &["/src"], "/copied", SymlinkPolicy::Preserve)
+            .await
+            .unwrap();
+        ops::copy_files_with_progress(
+            &backend,
+            &["/src"],
+            "/copied_progress",
+            SymlinkPolicy::Preserve,
+            continue_on_progress,
+        )
+        .await
+        .unwrap();
+        ops::copy_files_between(
+            &backend,
+            &StdBackend,
+            &["/src"],
+            local,
+            SymlinkPolicy::Preserve,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read(local_root.path().join("src/nested/two")).unwrap(),
+            b"22"
+        );
+
+        ops::move_files(
+            &backend,
+            &["/copied/src"],
+            "/moved",
+            SymlinkPolicy::Preserve,
+        )
+        .await
+        .unwrap();
+        ops::move_files_with_progress(
+            &backend,
+            &["/copied_progress/src"],
+            "/moved_progress",
+            SymlinkPolicy::Preserve,
+            continue_on_progress,
+        )
+        .await
+        .unwrap();
+        for dir in ["moved", "moved_progress"] {
+            assert_eq!(
+                std::fs::read(server.root.path().join(dir).join("src/nested/two")).unwrap(),
+                b"22"
+            );
+        }
+
+        let local_src = format!("{local}/src");
+        ops::move_files_between(
+            &StdBackend,
+            &backend,
+            &[local_src.as_str()],
+            "/copied",
+            SymlinkPolicy::Preserve,
+        )
+        .await
+        .unwrap();
+        ops::copy_files_between_with_progress(
+            &backend,
+            &StdBackend,
+            &["/copied/src"],
+            local,
+            SymlinkPolicy::Preserve,
+            continue_on_progress,
+        )
+        .await
+        .unwrap();
+        ops::move_files_between_with_progress(
+            &StdBackend,
+            &backend,
+            &[local_src.as_str()],
+            "/copied_progress",
+            SymlinkPolicy::Preserve,
+            continue_on_progress,
+        )
+        .await
+        .unwrap();
+
+        assert!(!local_root.path().join("src").exists());
+        for dir in ["copied", "copied_progress"] {
+            assert_eq!(
+                std::fs::read(server.root.path().join(dir).join("src/one")).unwrap(),
+                b"1"
+            );
+        }
+
+        backend.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pooled_ftp() {
+        let server = FTPServer::start().await;
+        let backend = server.builder().build_pool(2).await.unwrap();
+        std::fs::write(server.root.path().join("large"), vec![0; 1024 * 1024]).unwrap();
+
+        // A listing must not wait for the transfer occupying the other connection
+        let reader = backend.open_read("/large").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), backend.read_dir("/"))
+            .await
+            .expect("read_dir was blocked by an open transfer")
+            .unwrap();
+        drop(reader);
+
+        // Copies must not each take one connection and then wait for a second one
+        let copies = async {
+            tokio::join!(
+                backend.copy_file("/large", "/a", false),
+                backend.copy_file("/large", "/b", false),
+            )
+        };
+        let (a, b) = tokio::time::timeout(Duration::from_secs(10), copies)
+            .await
+            .expect("Concurrent copies deadlocked");
+        a.unwrap();
+        b.unwrap();
+
+        conformance::run_all(&backend, "").await;
+        backend.disconnect().await.unwrap();
+        assert!(backend.exists("/").await.is_err());
+
+        assert!(server.builder().build_pool(0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reconnect() {
+        let ftp_server = FTPServer::start().await;
+        let ftp = ftp_server
+            .builder()
+            .keepalive(Duration::from_millis(100))
+            .build()
+            .await
+            .unwrap();
+        let ftp_without_reconnect = ftp_server
+            .builder()
+            .reconnect(ReconnectPolicy::never())
+            .build()
+            .await
+            .unwrap();
+        let sftp_server = SFTPServer::start().await;
+        let sftp = sftp_server.connect().await;
+
+        ftp_server.drop_connections();
+        sftp_server.drop_connections();
+        // Without waiting the SSH session has not noticed yet, only the request itself fails
+        assert!(sftp.exists("/").await.unwrap());
+
+        sftp_server.drop_connections();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert!(ftp.exists("/").await.unwrap());
+        assert!(ftp_without_reconnect.exists("/").await.is_err());
+        assert!(sftp.exists("/").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let mut attempts = 0;
+        let result = policy
+            .retry(|| {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    match attempt {
+                        1 | 2 => Err(std::io::ErrorKind::ConnectionReset.into()),
+                        _ => Ok(attempt),
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+
+        let mut attempts = 0;
+        let result: Result<()> = policy
+            .retry(|| {
+                attempts += 1;
+                async { Err(Error::FileNonexistent("/missing".to_string())) }
+            })
+            .await;
+        assert!(result.unwrap_err().is_nonexistent_error());
+        assert_eq!(attempts, 1);
+
+        let backend = RetryBackend::new(MemoryBackend::new(), policy);
+        backend.create_dir("/root").await.unwrap();
+        conformance::run_all(&backend, "/root").await;
+    }
+
+    #[tokio::test]
+    async fn memory() {
+        let backend = MemoryBackend::new();
+
+        backend.create_dir("/src").await.unwrap();
+        backend.create_dir("/src/nested").await.unwrap();
+        backend.create_dir("/dest").await.unwrap();
+        backend
+            .create_file("/src/a.txt", false, Some(b"a"))
+            .await
+            .unwrap();
+        backend
+            .create_file("/src/nested/b.txt", false, Some(b"b"))
+            .await
+            .unwrap();
+        backend.create_symlink("/link", "/src/a.txt").await.unwrap();
+
+        assert!(backend
+            .create_file("/src/a.txt", false, None)
+            .await
+            .unwrap_err()
+            .is_already_exists_error());
+        assert_eq!(
+            backend.get_file_type("/link").await.unwrap(),
+            FileType::File
+        );
+        assert_eq!(backend.retrieve_file_content("/link").await.unwrap(), b"a");
+
+        backend.create_symlink("/self", "/self").await.unwrap();
+        assert!(matches!(
+            backend.get_file_type("/self").await.unwrap_err().root_cause(),
+            Error::SymlinkLoop(path) if path == "/self"
+        ));
+
+        ops::copy_files(&backend, &["/src"], "/dest", SymlinkPolicy::Preserve)
+            .await
+            .unwrap();
+        assert_eq!(
+            backend
+                .retrieve_file_content("/dest/src/nested/b.txt")
+                .await
+                .unwrap(),
+            b"b"
+        );
+        assert_eq!(backend.calculate_total_size(&["/dest"]).await.unwrap(), 2);
+
+        backend.remove_all(&["/dest/src"]).await.unwrap();
+        ops::move_files(&backend, &["/src"], "/dest", SymlinkPolicy::Preserve)
+            .await
+            .unwrap();
+        assert!(!backend.exists("/src").await.unwrap());
+        assert!(!backend.exists("/link").await.unwrap());
+        assert_eq!(backend.read_dir("/dest/src").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn symlink_policies() {
+        let backend = MemoryBackend::new();
+        let local_root = tempfile::tempdir().unwrap();
+        let local = local_root.path().to_str().unwrap();
+
+        backend.create_dir("/src").await.unwrap();
+        backend
+            .create_file("/src/a.txt", false, Some(b"a"))
+            .await
+            .unwrap();
+        backend.create_symlink("/src/link", "a.txt").await.unwrap();
+        for dir in ["/preserved", "/followed", "/skipped", "/moved"] {
+            backend.create_dir(dir).await.unwrap();
+        }
+
+        ops::copy_files(&backend, &["/src"], "/preserved", SymlinkPolicy::Preserve)
+            .await
+            .unwrap();
+        assert_eq!(
+            backend.read_link("/preserved/src/link").await.unwrap(),
+            "a.txt"
+        );
+
+        ops::copy_files(&backend, &["/src"], "/followed", SymlinkPolicy::Follow)
+            .await
+            .unwrap();
+        assert!(backend.read_link("/followed/src/link").await.is_err());
+        assert_eq!(
+            backend
+                .retrieve_file_content("/followed/src/link")
+                .await
+                .unwrap(),
+            b"a"
+        );
+
+        ops::copy_files(&backend, &["/src"], "/skipped", SymlinkPolicy::Skip)
+            .await
+            .unwrap();
+        assert!(!backend.exists("/skipped/src/link").await.unwrap());
+
+        let error = ops::copy_files(&backend, &["/src/link"], "/", SymlinkPolicy::Error)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Unsupported);
+
+        ops::copy_files_between(
+            &backend,
+            &StdBackend,
+            &["/src"],
+            local,
+            SymlinkPolicy::Preserve,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read_link(local_root.path().join("src/link")).unwrap(),
+            std::path::Path::new("a.txt")
+        );
+
+        // Moving a followed link leaves its target in place
+        ops::move_files(&backend, &["/src/link"], "/moved", SymlinkPolicy::Follow)
+            .await
+            .unwrap();
+        assert!(!backend.exists("/src/link").await.unwrap());
+        assert!(backend.exists("/src/a.txt").await.unwrap());
+        assert_eq!(
+            backend.retrieve_file_content("/moved/link").await.unwrap(),
+            b"a"
+        );
+
+        // So does moving a followed link to a directory
+        backend
+            .create_symlink("/src/dir_link", "/preserved")
+            .await
+            .unwrap();
+        ops::move_files(
+            &backend,
+            &["/src/dir_link"],
+            "/moved",
+            SymlinkPolicy::Follow,
+        )
+        .await
+        .unwrap();
+        assert!(!backend.exists("/src/dir_link").await.unwrap());
+        assert!(backend.exists("/preserved/src/a.txt").await.unwrap());
+        assert!(backend.exists("/moved/dir_link/src/a.txt").await.unwrap());
+
+        // Following a link back into the directory being copied has to stop
+        backend.create_symlink("/src/loop", "..").await.unwrap();
+        backend.create_dir("/looped").await.unwrap();
+        let error = ops::copy_files(&backend, &["/src"], "/looped", SymlinkPolicy::Follow)
+            .await
+            .unwrap_err();
+        assert!(matches!(error.root_cause(), Error::SymlinkLoop(_)));
+
+        StdBackend
+            .create_dir(&format!("{local}/loop"))
+            .await
+            .unwrap();
+        StdBackend
+            .create_symlink(&format!("{local}/loop/self"), ".")
+            .await
+            .unwrap();
+        let error = ops::copy_files_between(
+            &StdBackend,
+            &backend,
+            &[format!("{local}/loop").as_str()],
+            "/",
+            SymlinkPolicy::Follow,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error.root_cause(), Error::SymlinkLoop(_)));
+    }
+
+    #[tokio::test]
+    async fn error_kinds() {
+        let root = tempfile::tempdir().expect("Failed to create temporary directory");
+        let root = root.path().to_str().unwrap();
+        let ftp_server = FTPServer::start().await;
+        let ftp = ftp_server.connect().await;
+        let sftp_server = SFTPServer::start().await;
+        let sftp = sftp_server.connect().await;
+
+        for (backend, root) in [
+            (&StdBackend as &dyn FSBackend, root),
+            (&ftp, ""),
+            (&sftp, ""),
+        ] {
+            backend.create_dir(&format!("{root}/dir")).await.unwrap();
+            backend
+                .create_file(&format!("{root}/dir/file"), false, None)
+                .await
+                .unwrap();
+
+            for (result, kind) in [
+                (
+                    backend.read_dir(&format!("{root}/missing")).await.err(),
+                    ErrorKind::NotFound,
+                ),
+                (
+                    backend.create_dir(&format!("{root}/dir")).await.err(),
+                    ErrorKind::AlreadyExists,
+                ),
+            ] {
+                assert_eq!(result.unwrap().kind(), kind);
+            }
+        }
+
+        let error = StdBackend
+            .remove_dir(&format!("{root}/dir"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::DirectoryNotEmpty);
+        let error = ftp.remove_dir("/dir").await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::DirectoryNotEmpty);
+        // A 550 reply alone does not mean the file is missing
+        let error = ftp.retrieve_file_content("/dir").await.unwrap_err();
+        assert!(!error.is_nonexistent_error(), "unexpected error {error:?}");
+        // Running out of data is only a lost connection on a network stream
+        assert_eq!(
+            Error::from(std::io::ErrorKind::UnexpectedEof).kind(),
+            ErrorKind::Other
+        );
+
+        let missing = format!("{root}/missing");
+        let error = StdBackend.remove_file(&missing).await.unwrap_err();
+        assert_eq!(error.operation(), Some("remove_file"));
+        assert_eq!(error.paths(), std::slice::from_ref(&missing));
+        assert!(error.to_string().contains(&missing));
+        // The OS message survives, reachable through the source chain
+        let source = std::error::Error::source(&error).unwrap();
+        let io_error = std::error::Error::source(source).unwrap();
+        assert!(io_error.to_string().contains("os error 2"));
+
+        // Context does not hide what went wrong
+        let error = Error::AuthenticationFailed(SFTP_USER.to_string()).context("exists", &["/"]);
+        assert_eq!(error.kind(), ErrorKind::AuthenticationFailed);
+        assert!(matches!(error.root_cause(), Error::AuthenticationFailed(_)));
+        assert!(!error.is_transient_error());
+    }
+
+    #[tokio::test]
+    async fn std_conformance() {
+        let root = tempfile::tempdir().expect("Failed to create temporary directory");
+
+        conformance::run_all(&StdBackend, root.path().to_str().unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn memory_conformance() {
+        let backend = MemoryBackend::new();
+        backend.create_dir("/root").await.unwrap();
+
+        conformance::run_all(&backend, "/root").await;
     }
 }
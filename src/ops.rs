@@ -1,41 +1,73 @@
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::future::Future;
+use tokio::io::AsyncWriteExt;
 
 use crate::{
-    data::FileType,
-    error::{Error, Result},
-    util::extract_lowest_path_item,
+    data::{File, FileType, Metadata},
+    error::{Error, Result, ResultExt},
+    util::{extract_lowest_path_item, remove_lowest_path_item},
     FSBackend,
 };
 
-pub async fn move_files<S: AsRef<str>>(backend: &dyn FSBackend, from: &[S], to: S) -> Result<()> {
+// What happens to symlinks that are copied or moved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SymlinkPolicy {
+    // Recreate the link at the destination, pointing at the same target
+    #[default]
+    Preserve,
+    // Transfer whatever the link points to in its place
+    Follow,
+    Skip,
+    Error,
+}
+
+pub async fn move_files<S: AsRef<str>>(
+    backend: &dyn FSBackend,
+    from: &[S],
+    to: S,
+    symlinks: SymlinkPolicy,
+) -> Result<()> {
     let to = to.as_ref();
 
     if !backend.exists(to).await? {
         return Err(Error::FileNonexistent(to.to_string()));
     }
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_duplicate: Vec<(String, String)> = vec![];
+    let mut dirs_to_duplicate: Vec<PendingDir> = vec![];
+    // Followed links to directories, which are removed instead of the directories themselves
+    let mut links_to_remove = vec![];
 
     for path in from {
         let path = path.as_ref();
-
-        match backend.get_file_type(path).await? {
+        let file_type = backend.symlink_metadata(path).await?.r#type;
+        let Some(resolved_type) =
+            resolve_symlink(backend, path, file_type, symlinks, "move_files").await?
+        else {
+            continue;
+        };
+
+        match resolved_type {
             FileType::File | FileType::Symlink => {
-                backend
-                    .move_file(
-                        path,
-                        &format!("{to}/{}", extract_lowest_path_item(path)),
-                        false,
-                    )
-                    .await?
+                move_file_within(
+                    backend,
+                    path,
+                    &format!("{to}/{}", extract_lowest_path_item(path)),
+                    resolved_type != file_type,
+                    false,
+                    false,
+                )
+                .await?
+            }
+            FileType::Dir => {
+                let followed = resolved_type != file_type;
+                if followed {
+                    links_to_remove.push(path.to_string());
+                }
+                dirs_to_duplicate.push(PendingDir::new(backend, path, followed, symlinks).await?)
             }
-            FileType::Dir => dirs_to_duplicate.push((path.into(), "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            t => return Err(Error::CannotCopyOrMoveFileType(t).context("move_files", &[path])),
         }
     }
 
@@ -44,9 +76,7 @@ pub async fn move_files<S: AsRef<str>>(backend: &dyn FSBackend, from: &[S], to:
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_duplicate {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -54,26 +84,55 @@ pub async fn move_files<S: AsRef<str>>(backend: &dyn FSBackend, from: &[S], to:
                 }
             }
 
-            for file in backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
+            for file in backend.read_dir(&dir.path).await? {
+                let file_type = file.metadata.r#type;
+                let Some(resolved_type) =
+                    resolve_symlink(backend, &file.path, file_type, symlinks, "move_files").await?
+                else {
+                    continue;
+                };
+
+                match resolved_type {
                     FileType::File | FileType::Symlink => {
-                        backend
-                            .move_file(&file.path, &format!("{to_dir_path}/{}", file.name), false)
-                            .await?
+                        move_file_within(
+                            backend,
+                            &file.path,
+                            &format!("{to_dir_path}/{}", file.name),
+                            resolved_type != file_type,
+                            dir.through_link,
+                            false,
+                        )
+                        .await?
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        if followed && !dir.through_link {
+                            links_to_remove.push(file.path.clone());
+                        }
+                        new_dirs_to_copy.push(
+                            dir.child(backend, &file, followed, symlinks, "move_files")
+                                .await?,
+                        );
+                    }
+                    t => {
+                        return Err(
+                            Error::CannotCopyOrMoveFileType(t).context("move_files", &[&file.path])
+                        )
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
 
-            dirs_to_remove.push(dir.0);
+            if !dir.through_link {
+                dirs_to_remove.push(dir.path);
+            }
         }
 
         dirs_to_duplicate = new_dirs_to_copy
     }
 
+    for link in links_to_remove {
+        backend.remove_file(&link).await?;
+    }
     while let Some(dir) = dirs_to_remove.pop() {
         backend.remove_dir(&dir).await?;
     }
@@ -81,33 +140,43 @@ pub async fn move_files<S: AsRef<str>>(backend: &dyn FSBackend, from: &[S], to:
     Ok(())
 }
 
-pub async fn copy_files<S: AsRef<str>>(backend: &dyn FSBackend, from: &[S], to: S) -> Result<()> {
+pub async fn copy_files<S: AsRef<str>>(
+    backend: &dyn FSBackend,
+    from: &[S],
+    to: S,
+    symlinks: SymlinkPolicy,
+) -> Result<()> {
     let to = to.as_ref();
 
     if !backend.exists(to).await? {
         return Err(Error::FileNonexistent(to.to_string()));
     }
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_copy: Vec<(String, String)> = vec![];
+    let mut dirs_to_copy: Vec<PendingDir> = vec![];
 
     for path in from {
         let path = path.as_ref();
-
-        match backend.get_file_type(path).await? {
+        let file_type = backend.symlink_metadata(path).await?.r#type;
+        let Some(resolved_type) =
+            resolve_symlink(backend, path, file_type, symlinks, "copy_files").await?
+        else {
+            continue;
+        };
+
+        match resolved_type {
             FileType::File | FileType::Symlink => {
-                backend
-                    .copy_file(
-                        path,
-                        &format!("{to}/{}", extract_lowest_path_item(path)),
-                        false,
-                    )
-                    .await?
+                copy_file_within(
+                    backend,
+                    path,
+                    &format!("{to}/{}", extract_lowest_path_item(path)),
+                    resolved_type,
+                    false,
+                )
+                .await?
             }
-            FileType::Dir => dirs_to_copy.push((path.into(), "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            FileType::Dir => dirs_to_copy
+                .push(PendingDir::new(backend, path, resolved_type != file_type, symlinks).await?),
+            t => return Err(Error::CannotCopyOrMoveFileType(t).context("copy_files", &[path])),
         }
     }
 
@@ -115,9 +184,7 @@ pub async fn copy_files<S: AsRef<str>>(backend: &dyn FSBackend, from: &[S], to:
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_copy {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -125,17 +192,42 @@ pub async fn copy_files<S: AsRef<str>>(backend: &dyn FSBackend, from: &[S], to:
                 }
             }
 
-            for file in backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
+            for file in backend.read_dir(&dir.path).await? {
+                let Some(resolved_type) = resolve_symlink(
+                    backend,
+                    &file.path,
+                    file.metadata.r#type,
+                    symlinks,
+                    "copy_files",
+                )
+                .await?
+                else {
+                    continue;
+                };
+
+                match resolved_type {
                     FileType::File | FileType::Symlink => {
-                        backend
-                            .copy_file(&file.path, &format!("{to_dir_path}/{}", file.name), false)
-                            .await?
+                        copy_file_within(
+                            backend,
+                            &file.path,
+                            &format!("{to_dir_path}/{}", file.name),
+                            resolved_type,
+                            false,
+                        )
+                        .await?
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        new_dirs_to_copy.push(
+                            dir.child(backend, &file, followed, symlinks, "copy_files")
+                                .await?,
+                        );
+                    }
+                    t => {
+                        return Err(
+                            Error::CannotCopyOrMoveFileType(t).context("copy_files", &[&file.path])
+                        )
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
         }
@@ -233,9 +325,10 @@ pub async fn move_files_with_progress<
     backend: &dyn FSBackend,
     from: &[S],
     to: S,
+    symlinks: SymlinkPolicy,
     progress_handler: impl Fn(TransitProgress) -> Fut,
 ) -> Result<()> {
-    let from: Vec<&str> = from.into_iter().map(|path| path.as_ref()).collect();
+    let from: Vec<&str> = from.iter().map(|path| path.as_ref()).collect();
     let to = to.as_ref();
 
     if !backend.exists(to).await? {
@@ -247,16 +340,29 @@ pub async fn move_files_with_progress<
         ..Default::default()
     };
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_duplicate: Vec<(String, String)> = vec![];
-
-    for file in backend.retrieve_files(&from[..]).await? {
-        match file.metadata.r#type {
+    let mut dirs_to_duplicate: Vec<PendingDir> = vec![];
+    // Followed links to directories, which are removed instead of the directories themselves
+    let mut links_to_remove = vec![];
+
+    for file in backend.retrieve_files(&from[..], false).await? {
+        let Some(resolved_type) = resolve_symlink(
+            backend,
+            &file.path,
+            file.metadata.r#type,
+            symlinks,
+            "move_files_with_progress",
+        )
+        .await?
+        else {
+            continue;
+        };
+        let followed = resolved_type != file.metadata.r#type;
+
+        match resolved_type {
             FileType::File | FileType::Symlink => {
                 let file_dest = format!("{to}/{}", extract_lowest_path_item(&file.path));
-                let result = backend.move_file(&file.path, &file_dest, false).await;
+                let result =
+                    move_file_within(backend, &file.path, &file_dest, followed, false, false).await;
 
                 let response = update_and_notify_progress_handler(
                     &mut progress,
@@ -271,12 +377,28 @@ pub async fn move_files_with_progress<
 
                 resolve_progress_handler_response!(
                     response,
-                    || backend.move_file(&file.path, &file_dest, true),
+                    || move_file_within(backend, &file.path, &file_dest, followed, false, true),
                     progress
                 );
             }
-            FileType::Dir => dirs_to_duplicate.push((file.path, "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            FileType::Dir => {
+                if followed {
+                    links_to_remove.push(file.path.clone());
+                }
+                dirs_to_duplicate.push(
+                    PendingDir::new(
+                        backend,
+                        &file.path,
+                        resolved_type != file.metadata.r#type,
+                        symlinks,
+                    )
+                    .await?,
+                )
+            }
+            t => {
+                return Err(Error::CannotCopyOrMoveFileType(t)
+                    .context("move_files_with_progress", &[&file.path]))
+            }
         }
     }
 
@@ -285,9 +407,7 @@ pub async fn move_files_with_progress<
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_duplicate {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -295,11 +415,32 @@ pub async fn move_files_with_progress<
                 }
             }
 
-            for file in backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
+            for file in backend.read_dir(&dir.path).await? {
+                let Some(resolved_type) = resolve_symlink(
+                    backend,
+                    &file.path,
+                    file.metadata.r#type,
+                    symlinks,
+                    "move_files_with_progress",
+                )
+                .await?
+                else {
+                    continue;
+                };
+                let followed = resolved_type != file.metadata.r#type;
+
+                match resolved_type {
                     FileType::File | FileType::Symlink => {
                         let file_dest = format!("{to_dir_path}/{}", file.name);
-                        let result = backend.move_file(&file.path, &file_dest, false).await;
+                        let result = move_file_within(
+                            backend,
+                            &file.path,
+                            &file_dest,
+                            followed,
+                            dir.through_link,
+                            false,
+                        )
+                        .await;
 
                         let response = update_and_notify_progress_handler(
                             &mut progress,
@@ -314,23 +455,51 @@ pub async fn move_files_with_progress<
 
                         resolve_progress_handler_response!(
                             response,
-                            || backend.move_file(&file.path, &file_dest, true),
+                            || move_file_within(
+                                backend,
+                                &file.path,
+                                &file_dest,
+                                followed,
+                                dir.through_link,
+                                true
+                            ),
                             progress
                         );
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        if followed && !dir.through_link {
+                            links_to_remove.push(file.path.clone());
+                        }
+                        new_dirs_to_copy.push(
+                            dir.child(
+                                backend,
+                                &file,
+                                followed,
+                                symlinks,
+                                "move_files_with_progress",
+                            )
+                            .await?,
+                        );
+                    }
+                    t => {
+                        return Err(Error::CannotCopyOrMoveFileType(t)
+                            .context("move_files_with_progress", &[&file.path]))
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
 
-            dirs_to_remove.push(dir.0);
+            if !dir.through_link {
+                dirs_to_remove.push(dir.path);
+            }
         }
 
         dirs_to_duplicate = new_dirs_to_copy
     }
 
+    for link in links_to_remove {
+        backend.remove_file(&link).await?;
+    }
     while let Some(dir) = dirs_to_remove.pop() {
         backend.remove_dir(&dir).await?;
     }
@@ -345,9 +514,10 @@ pub async fn copy_files_with_progress<
     backend: &dyn FSBackend,
     from: &[S],
     to: S,
+    symlinks: SymlinkPolicy,
     progress_handler: impl Fn(TransitProgress) -> Fut,
 ) -> Result<()> {
-    let from: Vec<&str> = from.into_iter().map(|path| path.as_ref()).collect();
+    let from: Vec<&str> = from.iter().map(|path| path.as_ref()).collect();
     let to = to.as_ref();
 
     if !backend.exists(to).await? {
@@ -359,16 +529,26 @@ pub async fn copy_files_with_progress<
         ..Default::default()
     };
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_copy: Vec<(String, String)> = vec![];
-
-    for file in backend.retrieve_files(&from[..]).await? {
-        match file.metadata.r#type {
-            FileType::File => {
+    let mut dirs_to_copy: Vec<PendingDir> = vec![];
+
+    for file in backend.retrieve_files(&from[..], false).await? {
+        let Some(resolved_type) = resolve_symlink(
+            backend,
+            &file.path,
+            file.metadata.r#type,
+            symlinks,
+            "copy_files_with_progress",
+        )
+        .await?
+        else {
+            continue;
+        };
+
+        match resolved_type {
+            FileType::File | FileType::Symlink => {
                 let file_dest = format!("{to}/{}", extract_lowest_path_item(&file.path));
-                let result = backend.copy_file(&file.path, &file_dest, false).await;
+                let result =
+                    copy_file_within(backend, &file.path, &file_dest, resolved_type, false).await;
 
                 let response = update_and_notify_progress_handler(
                     &mut progress,
@@ -383,12 +563,23 @@ pub async fn copy_files_with_progress<
 
                 resolve_progress_handler_response!(
                     response,
-                    || backend.copy_file(&file.path, &file_dest, true),
+                    || copy_file_within(backend, &file.path, &file_dest, resolved_type, true),
                     progress
                 );
             }
-            FileType::Dir => dirs_to_copy.push((file.path, "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            FileType::Dir => dirs_to_copy.push(
+                PendingDir::new(
+                    backend,
+                    &file.path,
+                    resolved_type != file.metadata.r#type,
+                    symlinks,
+                )
+                .await?,
+            ),
+            t => {
+                return Err(Error::CannotCopyOrMoveFileType(t)
+                    .context("copy_files_with_progress", &[&file.path]))
+            }
         }
     }
 
@@ -396,9 +587,7 @@ pub async fn copy_files_with_progress<
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_copy {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -406,11 +595,25 @@ pub async fn copy_files_with_progress<
                 }
             }
 
-            for file in backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
-                    FileType::File => {
+            for file in backend.read_dir(&dir.path).await? {
+                let Some(resolved_type) = resolve_symlink(
+                    backend,
+                    &file.path,
+                    file.metadata.r#type,
+                    symlinks,
+                    "copy_files_with_progress",
+                )
+                .await?
+                else {
+                    continue;
+                };
+
+                match resolved_type {
+                    FileType::File | FileType::Symlink => {
                         let file_dest = format!("{to_dir_path}/{}", file.name);
-                        let result = backend.copy_file(&file.path, &file_dest, false).await;
+                        let result =
+                            copy_file_within(backend, &file.path, &file_dest, resolved_type, false)
+                                .await;
 
                         let response = update_and_notify_progress_handler(
                             &mut progress,
@@ -425,14 +628,33 @@ pub async fn copy_files_with_progress<
 
                         resolve_progress_handler_response!(
                             response,
-                            || backend.move_file(&file.path, &file_dest, true),
+                            || copy_file_within(
+                                backend,
+                                &file.path,
+                                &file_dest,
+                                resolved_type,
+                                true
+                            ),
                             progress
                         );
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        new_dirs_to_copy.push(
+                            dir.child(
+                                backend,
+                                &file,
+                                followed,
+                                symlinks,
+                                "copy_files_with_progress",
+                            )
+                            .await?,
+                        );
+                    }
+                    t => {
+                        return Err(Error::CannotCopyOrMoveFileType(t)
+                            .context("copy_files_with_progress", &[&file.path]))
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
         }
@@ -448,6 +670,7 @@ pub async fn move_files_between<S: AsRef<str>>(
     to_backend: &dyn FSBackend,
     from: &[S],
     to: S,
+    symlinks: SymlinkPolicy,
 ) -> Result<()> {
     let to = to.as_ref();
 
@@ -455,27 +678,52 @@ pub async fn move_files_between<S: AsRef<str>>(
         return Err(Error::FileNonexistent(to.to_string()));
     }
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_duplicate: Vec<(String, String)> = vec![];
+    let mut dirs_to_duplicate: Vec<PendingDir> = vec![];
+    // Followed links to directories, which are removed instead of the directories themselves
+    let mut links_to_remove = vec![];
 
     for path in from {
         let path = path.as_ref();
-
-        match from_backend.get_file_type(path).await? {
-            FileType::File => {
+        let file_type = from_backend.symlink_metadata(path).await?.r#type;
+        let Some(resolved_type) = resolve_symlink(
+            from_backend,
+            path,
+            file_type,
+            symlinks,
+            "move_files_between",
+        )
+        .await?
+        else {
+            continue;
+        };
+
+        match resolved_type {
+            FileType::File | FileType::Symlink => {
                 move_file_between(
                     from_backend,
                     to_backend,
                     path,
                     &format!("{to}/{}", extract_lowest_path_item(path)),
+                    resolved_type,
+                    false,
                     false,
                 )
                 .await?;
             }
-            FileType::Dir => dirs_to_duplicate.push((path.into(), "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            FileType::Dir => {
+                if resolved_type != file_type {
+                    links_to_remove.push(path.to_string());
+                }
+                dirs_to_duplicate.push(
+                    PendingDir::new(from_backend, path, resolved_type != file_type, symlinks)
+                        .await?,
+                )
+            }
+            t => {
+                return Err(
+                    Error::CannotCopyOrMoveFileType(t).context("move_files_between", &[path])
+                )
+            }
         }
     }
 
@@ -484,9 +732,7 @@ pub async fn move_files_between<S: AsRef<str>>(
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_duplicate {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = to_backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -494,31 +740,67 @@ pub async fn move_files_between<S: AsRef<str>>(
                 }
             }
 
-            for file in from_backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
-                    FileType::File => {
+            for file in from_backend.read_dir(&dir.path).await? {
+                let file_type = file.metadata.r#type;
+                let Some(resolved_type) = resolve_symlink(
+                    from_backend,
+                    &file.path,
+                    file_type,
+                    symlinks,
+                    "move_files_between",
+                )
+                .await?
+                else {
+                    continue;
+                };
+
+                match resolved_type {
+                    FileType::File | FileType::Symlink => {
                         move_file_between(
                             from_backend,
                             to_backend,
                             &file.path,
                             &format!("{to_dir_path}/{}", file.name),
+                            resolved_type,
+                            dir.through_link,
                             false,
                         )
                         .await?;
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        if followed && !dir.through_link {
+                            links_to_remove.push(file.path.clone());
+                        }
+                        new_dirs_to_copy.push(
+                            dir.child(
+                                from_backend,
+                                &file,
+                                followed,
+                                symlinks,
+                                "move_files_between",
+                            )
+                            .await?,
+                        );
+                    }
+                    t => {
+                        return Err(Error::CannotCopyOrMoveFileType(t)
+                            .context("move_files_between", &[&file.path]))
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
 
-            dirs_to_remove.push(dir.0);
+            if !dir.through_link {
+                dirs_to_remove.push(dir.path);
+            }
         }
 
         dirs_to_duplicate = new_dirs_to_copy
     }
 
+    for link in links_to_remove {
+        from_backend.remove_file(&link).await?;
+    }
     while let Some(dir) = dirs_to_remove.pop() {
         from_backend.remove_dir(&dir).await?;
     }
@@ -531,6 +813,7 @@ pub async fn copy_files_between<S: AsRef<str>>(
     to_backend: &dyn FSBackend,
     from: &[S],
     to: S,
+    symlinks: SymlinkPolicy,
 ) -> Result<()> {
     let to = to.as_ref();
 
@@ -538,27 +821,43 @@ pub async fn copy_files_between<S: AsRef<str>>(
         return Err(Error::FileNonexistent(to.to_string()));
     }
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_copy: Vec<(String, String)> = vec![];
+    let mut dirs_to_copy: Vec<PendingDir> = vec![];
 
     for path in from {
         let path = path.as_ref();
-
-        match from_backend.get_file_type(path).await? {
+        let file_type = from_backend.symlink_metadata(path).await?.r#type;
+        let Some(resolved_type) = resolve_symlink(
+            from_backend,
+            path,
+            file_type,
+            symlinks,
+            "copy_files_between",
+        )
+        .await?
+        else {
+            continue;
+        };
+
+        match resolved_type {
             FileType::File | FileType::Symlink => {
-                copy_file_between(
+                copy_file_or_link_between(
                     from_backend,
                     to_backend,
                     path,
                     &format!("{to}/{}", extract_lowest_path_item(path)),
+                    resolved_type,
                     false,
                 )
                 .await?
             }
-            FileType::Dir => dirs_to_copy.push((path.into(), "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            FileType::Dir => dirs_to_copy.push(
+                PendingDir::new(from_backend, path, resolved_type != file_type, symlinks).await?,
+            ),
+            t => {
+                return Err(
+                    Error::CannotCopyOrMoveFileType(t).context("copy_files_between", &[path])
+                )
+            }
         }
     }
 
@@ -566,9 +865,7 @@ pub async fn copy_files_between<S: AsRef<str>>(
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_copy {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = to_backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -576,22 +873,48 @@ pub async fn copy_files_between<S: AsRef<str>>(
                 }
             }
 
-            for file in from_backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
+            for file in from_backend.read_dir(&dir.path).await? {
+                let Some(resolved_type) = resolve_symlink(
+                    from_backend,
+                    &file.path,
+                    file.metadata.r#type,
+                    symlinks,
+                    "copy_files_between",
+                )
+                .await?
+                else {
+                    continue;
+                };
+
+                match resolved_type {
                     FileType::File | FileType::Symlink => {
-                        copy_file_between(
+                        copy_file_or_link_between(
                             from_backend,
                             to_backend,
                             &file.path,
                             &format!("{to_dir_path}/{}", file.name),
+                            resolved_type,
                             false,
                         )
                         .await?;
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        new_dirs_to_copy.push(
+                            dir.child(
+                                from_backend,
+                                &file,
+                                followed,
+                                symlinks,
+                                "copy_files_between",
+                            )
+                            .await?,
+                        );
+                    }
+                    t => {
+                        return Err(Error::CannotCopyOrMoveFileType(t)
+                            .context("copy_files_between", &[&file.path]))
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
         }
@@ -610,9 +933,10 @@ pub async fn move_files_between_with_progress<
     to_backend: &dyn FSBackend,
     from: &[S],
     to: S,
+    symlinks: SymlinkPolicy,
     progress_handler: impl Fn(TransitProgress) -> Fut,
 ) -> Result<()> {
-    let from: Vec<&str> = from.into_iter().map(|path| path.as_ref()).collect();
+    let from: Vec<&str> = from.iter().map(|path| path.as_ref()).collect();
     let to = to.as_ref();
 
     if !to_backend.exists(to).await? {
@@ -624,18 +948,36 @@ pub async fn move_files_between_with_progress<
         ..Default::default()
     };
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_duplicate: Vec<(String, String)> = vec![];
-
-    for file in from_backend.retrieve_files(&from[..]).await? {
-        match file.metadata.r#type {
-            FileType::File => {
+    let mut dirs_to_duplicate: Vec<PendingDir> = vec![];
+    // Followed links to directories, which are removed instead of the directories themselves
+    let mut links_to_remove = vec![];
+
+    for file in from_backend.retrieve_files(&from[..], false).await? {
+        let Some(resolved_type) = resolve_symlink(
+            from_backend,
+            &file.path,
+            file.metadata.r#type,
+            symlinks,
+            "move_files_between_with_progress",
+        )
+        .await?
+        else {
+            continue;
+        };
+
+        match resolved_type {
+            FileType::File | FileType::Symlink => {
                 let file_dest = format!("{to}/{}", extract_lowest_path_item(&file.path));
-                let result =
-                    move_file_between(from_backend, to_backend, &file.path, &file_dest, false)
-                        .await;
+                let result = move_file_between(
+                    from_backend,
+                    to_backend,
+                    &file.path,
+                    &file_dest,
+                    resolved_type,
+                    false,
+                    false,
+                )
+                .await;
 
                 let response = update_and_notify_progress_handler(
                     &mut progress,
@@ -650,12 +992,36 @@ pub async fn move_files_between_with_progress<
 
                 resolve_progress_handler_response!(
                     response,
-                    || move_file_between(from_backend, to_backend, &file.path, &file_dest, true),
+                    || move_file_between(
+                        from_backend,
+                        to_backend,
+                        &file.path,
+                        &file_dest,
+                        resolved_type,
+                        false,
+                        true
+                    ),
                     progress
                 );
             }
-            FileType::Dir => dirs_to_duplicate.push((file.path, "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            FileType::Dir => {
+                if resolved_type != file.metadata.r#type {
+                    links_to_remove.push(file.path.clone());
+                }
+                dirs_to_duplicate.push(
+                    PendingDir::new(
+                        from_backend,
+                        &file.path,
+                        resolved_type != file.metadata.r#type,
+                        symlinks,
+                    )
+                    .await?,
+                )
+            }
+            t => {
+                return Err(Error::CannotCopyOrMoveFileType(t)
+                    .context("move_files_between_with_progress", &[&file.path]))
+            }
         }
     }
 
@@ -664,9 +1030,7 @@ pub async fn move_files_between_with_progress<
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_duplicate {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = to_backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -674,8 +1038,20 @@ pub async fn move_files_between_with_progress<
                 }
             }
 
-            for file in from_backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
+            for file in from_backend.read_dir(&dir.path).await? {
+                let Some(resolved_type) = resolve_symlink(
+                    from_backend,
+                    &file.path,
+                    file.metadata.r#type,
+                    symlinks,
+                    "move_files_between_with_progress",
+                )
+                .await?
+                else {
+                    continue;
+                };
+
+                match resolved_type {
                     FileType::File | FileType::Symlink => {
                         let file_dest = format!("{to_dir_path}/{}", file.name);
                         let result = move_file_between(
@@ -683,6 +1059,8 @@ pub async fn move_files_between_with_progress<
                             to_backend,
                             &file.path,
                             &file_dest,
+                            resolved_type,
+                            dir.through_link,
                             false,
                         )
                         .await;
@@ -705,24 +1083,47 @@ pub async fn move_files_between_with_progress<
                                 to_backend,
                                 &file.path,
                                 &file_dest,
+                                resolved_type,
+                                dir.through_link,
                                 true
                             ),
                             progress
                         );
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        if followed && !dir.through_link {
+                            links_to_remove.push(file.path.clone());
+                        }
+                        new_dirs_to_copy.push(
+                            dir.child(
+                                from_backend,
+                                &file,
+                                followed,
+                                symlinks,
+                                "move_files_between_with_progress",
+                            )
+                            .await?,
+                        );
+                    }
+                    t => {
+                        return Err(Error::CannotCopyOrMoveFileType(t)
+                            .context("move_files_between_with_progress", &[&file.path]))
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
 
-            dirs_to_remove.push(dir.0);
+            if !dir.through_link {
+                dirs_to_remove.push(dir.path);
+            }
         }
 
         dirs_to_duplicate = new_dirs_to_copy
     }
 
+    for link in links_to_remove {
+        from_backend.remove_file(&link).await?;
+    }
     while let Some(dir) = dirs_to_remove.pop() {
         from_backend.remove_dir(&dir).await?;
     }
@@ -738,9 +1139,10 @@ pub async fn copy_files_between_with_progress<
     to_backend: &dyn FSBackend,
     from: &[S],
     to: S,
+    symlinks: SymlinkPolicy,
     progress_handler: impl Fn(TransitProgress) -> Fut,
 ) -> Result<()> {
-    let from: Vec<&str> = from.into_iter().map(|path| path.as_ref()).collect();
+    let from: Vec<&str> = from.iter().map(|path| path.as_ref()).collect();
     let to = to.as_ref();
 
     if !to_backend.exists(to).await? {
@@ -752,18 +1154,33 @@ pub async fn copy_files_between_with_progress<
         ..Default::default()
     };
 
-    // The first string in the tuple represents the origin path, the second
-    // represents the directories that encapsulate it relative to the lowest
-    // directory in the from path.
-    let mut dirs_to_copy: Vec<(String, String)> = vec![];
-
-    for file in from_backend.retrieve_files(&from[..]).await? {
-        match file.metadata.r#type {
-            FileType::File => {
+    let mut dirs_to_copy: Vec<PendingDir> = vec![];
+
+    for file in from_backend.retrieve_files(&from[..], false).await? {
+        let Some(resolved_type) = resolve_symlink(
+            from_backend,
+            &file.path,
+            file.metadata.r#type,
+            symlinks,
+            "copy_files_between_with_progress",
+        )
+        .await?
+        else {
+            continue;
+        };
+
+        match resolved_type {
+            FileType::File | FileType::Symlink => {
                 let file_dest = format!("{to}/{}", extract_lowest_path_item(&file.path));
-                let result =
-                    copy_file_between(from_backend, to_backend, &file.path, &file_dest, false)
-                        .await;
+                let result = copy_file_or_link_between(
+                    from_backend,
+                    to_backend,
+                    &file.path,
+                    &file_dest,
+                    resolved_type,
+                    false,
+                )
+                .await;
 
                 let response = update_and_notify_progress_handler(
                     &mut progress,
@@ -778,12 +1195,30 @@ pub async fn copy_files_between_with_progress<
 
                 resolve_progress_handler_response!(
                     response,
-                    || copy_file_between(from_backend, to_backend, &file.path, &file_dest, true),
+                    || copy_file_or_link_between(
+                        from_backend,
+                        to_backend,
+                        &file.path,
+                        &file_dest,
+                        resolved_type,
+                        true
+                    ),
                     progress
                 );
             }
-            FileType::Dir => dirs_to_copy.push((file.path, "/".into())),
-            t => return Err(Error::CannotCopyOrMoveFileType(t)),
+            FileType::Dir => dirs_to_copy.push(
+                PendingDir::new(
+                    from_backend,
+                    &file.path,
+                    resolved_type != file.metadata.r#type,
+                    symlinks,
+                )
+                .await?,
+            ),
+            t => {
+                return Err(Error::CannotCopyOrMoveFileType(t)
+                    .context("copy_files_between_with_progress", &[&file.path]))
+            }
         }
     }
 
@@ -791,9 +1226,7 @@ pub async fn copy_files_between_with_progress<
         let mut new_dirs_to_copy = vec![];
 
         for dir in dirs_to_copy {
-            let parent_dirs = dir.1;
-            let dir_name = extract_lowest_path_item(&dir.0);
-            let to_dir_path = format!("{to}{parent_dirs}{dir_name}");
+            let to_dir_path = dir.destination(to);
 
             if let Err(error) = to_backend.create_dir(&to_dir_path).await {
                 if !error.is_already_exists_error() {
@@ -801,11 +1234,31 @@ pub async fn copy_files_between_with_progress<
                 }
             }
 
-            for file in from_backend.read_dir(&dir.0).await? {
-                match file.metadata.r#type {
-                    FileType::File => {
+            for file in from_backend.read_dir(&dir.path).await? {
+                let Some(resolved_type) = resolve_symlink(
+                    from_backend,
+                    &file.path,
+                    file.metadata.r#type,
+                    symlinks,
+                    "copy_files_between_with_progress",
+                )
+                .await?
+                else {
+                    continue;
+                };
+
+                match resolved_type {
+                    FileType::File | FileType::Symlink => {
                         let file_dest = format!("{to_dir_path}/{}", file.name);
-                        let result = from_backend.copy_file(&file.path, &file_dest, false).await;
+                        let result = copy_file_or_link_between(
+                            from_backend,
+                            to_backend,
+                            &file.path,
+                            &file_dest,
+                            resolved_type,
+                            false,
+                        )
+                        .await;
 
                         let response = update_and_notify_progress_handler(
                             &mut progress,
@@ -820,20 +1273,34 @@ pub async fn copy_files_between_with_progress<
 
                         resolve_progress_handler_response!(
                             response,
-                            || copy_file_between(
+                            || copy_file_or_link_between(
                                 from_backend,
                                 to_backend,
                                 &file.path,
                                 &file_dest,
+                                resolved_type,
                                 true
                             ),
                             progress
                         );
                     }
                     FileType::Dir => {
-                        new_dirs_to_copy.push((file.path, format!("{parent_dirs}{dir_name}/")));
+                        let followed = resolved_type != file.metadata.r#type;
+                        new_dirs_to_copy.push(
+                            dir.child(
+                                from_backend,
+                                &file,
+                                followed,
+                                symlinks,
+                                "copy_files_between_with_progress",
+                            )
+                            .await?,
+                        );
+                    }
+                    t => {
+                        return Err(Error::CannotCopyOrMoveFileType(t)
+                            .context("copy_files_between_with_progress", &[&file.path]))
                     }
-                    t => return Err(Error::CannotCopyOrMoveFileType(t)),
                 }
             }
         }
@@ -844,18 +1311,251 @@ pub async fn copy_files_between_with_progress<
     Ok(())
 }
 
+// A directory that is yet to be transferred
+struct PendingDir {
+    path: String,
+    // The directories that encapsulate it relative to the lowest directory in the from path
+    parent_dirs: String,
+    // Reached through a followed link, so it belongs to the link target, which is only copied
+    through_link: bool,
+    // Only tracked when following symlinks: where the directory is with the followed links
+    // resolved, what it and the directories above it are and how many links led to it
+    resolved_path: String,
+    ancestors: Vec<DirIdentity>,
+    followed_links: usize,
+}
+
+// Directories are told apart by device and inode, or by their resolved path where a backend does
+// not know inodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DirIdentity {
+    Inode { device: u64, inode: u64 },
+    Path(String),
+}
+
+// Like Linux, give up after following this many links to reach a single directory
+const MAX_FOLLOWED_LINKS: usize = 40;
+
+impl PendingDir {
+    async fn new(
+        backend: &dyn FSBackend,
+        path: &str,
+        followed: bool,
+        symlinks: SymlinkPolicy,
+    ) -> Result<Self> {
+        let mut dir = Self {
+            path: path.to_string(),
+            parent_dirs: "/".into(),
+            through_link: followed,
+            resolved_path: path.to_string(),
+            ancestors: vec![],
+            followed_links: followed as usize,
+        };
+
+        if followed {
+            dir.resolved_path =
+                link_destination(backend, path, &remove_lowest_path_item(path)).await?;
+        }
+        if symlinks == SymlinkPolicy::Follow {
+            dir.ancestors.push(dir.identity(backend).await?);
+        }
+        Ok(dir)
+    }
+
+    // Errors when following a link leads back into a directory that is already being transferred,
+    // which would otherwise be transferred forever
+    async fn child(
+        &self,
+        backend: &dyn FSBackend,
+        file: &File,
+        followed: bool,
+        symlinks: SymlinkPolicy,
+        operation: &'static str,
+    ) -> Result<Self> {
+        let mut dir = Self {
+            path: file.path.clone(),
+            parent_dirs: format!(
+                "{}{}/",
+                self.parent_dirs,
+                extract_lowest_path_item(&self.path)
+            ),
+            through_link: self.through_link || followed,
+            resolved_path: format!("{}/{}", self.resolved_path, file.name),
+            ancestors: self.ancestors.clone(),
+            followed_links: self.followed_links + followed as usize,
+        };
+
+        if followed {
+            dir.resolved_path = link_destination(backend, &file.path, &self.resolved_path).await?;
+        }
+        if symlinks == SymlinkPolicy::Follow {
+            let identity = dir.identity(backend).await?;
+            if dir.followed_links > MAX_FOLLOWED_LINKS || dir.ancestors.contains(&identity) {
+                return Err(Error::SymlinkLoop(file.path.clone()).context(operation, &[&file.path]));
+            }
+            dir.ancestors.push(identity);
+        }
+        Ok(dir)
+    }
+
+    async fn identity(&self, backend: &dyn FSBackend) -> Result<DirIdentity> {
+        let files = backend.retrieve_files(&[&self.path], true).await?;
+
+        Ok(match files.first().map(|file| &file.metadata) {
+            Some(Metadata {
+                device: Some(device),
+                inode: Some(inode),
+                ..
+            }) => DirIdentity::Inode {
+                device: *device,
+                inode: *inode,
+            },
+            _ => DirIdentity::Path(normalize_path(&self.resolved_path)),
+        })
+    }
+
+    fn destination(&self, to: &str) -> String {
+        format!(
+            "{to}{}{}",
+            self.parent_dirs,
+            extract_lowest_path_item(&self.path)
+        )
+    }
+}
+
+// Where the link at `path` leads, resolving a relative target against `parent`, the directory the
+// link is in. Links in the target itself are left as they are.
+async fn link_destination(backend: &dyn FSBackend, path: &str, parent: &str) -> Result<String> {
+    let target = backend.read_link(path).await?;
+
+    if target.starts_with('/') {
+        Ok(normalize_path(&target))
+    } else {
+        Ok(normalize_path(&format!("{parent}/{target}")))
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let mut components = vec![];
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+
+    format!("/{}", components.join("/"))
+}
+
+// The type a file is transferred as under the policy, None if it is skipped
+async fn resolve_symlink(
+    backend: &dyn FSBackend,
+    path: &str,
+    file_type: FileType,
+    symlinks: SymlinkPolicy,
+    operation: &'static str,
+) -> Result<Option<FileType>> {
+    match (file_type, symlinks) {
+        (FileType::Symlink, SymlinkPolicy::Follow) => backend.get_file_type(path).await.map(Some),
+        (FileType::Symlink, SymlinkPolicy::Skip) => Ok(None),
+        (FileType::Symlink, SymlinkPolicy::Error) => {
+            Err(Error::CannotCopyOrMoveFileType(file_type).context(operation, &[path]))
+        }
+        _ => Ok(Some(file_type)),
+    }
+}
+
+// Renaming a symlink moves the link itself, so a followed one is replaced by a copy of its target.
+// Files reached through a followed link to a directory belong to its target and are only copied.
+async fn move_file_within(
+    backend: &dyn FSBackend,
+    from: &str,
+    to: &str,
+    followed: bool,
+    through_link: bool,
+    overwrite: bool,
+) -> Result<()> {
+    if through_link {
+        backend.copy_file(from, to, overwrite).await
+    } else if followed {
+        backend.copy_file(from, to, overwrite).await?;
+        backend.remove_file(from).await
+    } else {
+        backend.move_file(from, to, overwrite).await
+    }
+}
+
+async fn copy_file_within(
+    backend: &dyn FSBackend,
+    from: &str,
+    to: &str,
+    file_type: FileType,
+    overwrite: bool,
+) -> Result<()> {
+    if file_type == FileType::Symlink {
+        copy_symlink_between(backend, backend, from, to, overwrite).await
+    } else {
+        backend.copy_file(from, to, overwrite).await
+    }
+}
+
 async fn move_file_between(
     from_backend: &dyn FSBackend,
     to_backend: &dyn FSBackend,
     from: &str,
     to: &str,
+    file_type: FileType,
+    through_link: bool,
     overwrite: bool,
 ) -> Result<()> {
-    copy_file_between(from_backend, to_backend, from, to, overwrite).await?;
-    from_backend.remove_file(from).await?;
+    copy_file_or_link_between(from_backend, to_backend, from, to, file_type, overwrite).await?;
+    // Files reached through a followed link to a directory belong to its target
+    if !through_link {
+        from_backend.remove_file(from).await?;
+    }
     Ok(())
 }
 
+async fn copy_file_or_link_between(
+    from_backend: &dyn FSBackend,
+    to_backend: &dyn FSBackend,
+    from: &str,
+    to: &str,
+    file_type: FileType,
+    overwrite: bool,
+) -> Result<()> {
+    if file_type == FileType::Symlink {
+        copy_symlink_between(from_backend, to_backend, from, to, overwrite).await
+    } else {
+        copy_file_between(from_backend, to_backend, from, to, overwrite).await
+    }
+}
+
+// The target is copied verbatim, so relative targets resolve against the new location
+async fn copy_symlink_between(
+    from_backend: &dyn FSBackend,
+    to_backend: &dyn FSBackend,
+    from: &str,
+    to: &str,
+    overwrite: bool,
+) -> Result<()> {
+    let target = from_backend.read_link(from).await?;
+
+    if overwrite {
+        if let Err(error) = to_backend.remove_file(to).await {
+            if !error.is_nonexistent_error() {
+                return Err(error);
+            }
+        }
+    }
+
+    to_backend.create_symlink(to, &target).await
+}
+
 async fn copy_file_between(
     from_backend: &dyn FSBackend,
     to_backend: &dyn FSBackend,
@@ -863,9 +1563,21 @@ async fn copy_file_between(
     to: &str,
     overwrite: bool,
 ) -> Result<()> {
-    let contents = from_backend.retrieve_file_content(from).await?;
-    to_backend
-        .create_file(to, overwrite, Some(&contents))
-        .await?;
+    // A backend on a single connection, like `FTPBackend`, holds it for as long as a reader is
+    // open, so opening a writer on the same backend would wait forever
+    if std::ptr::addr_eq(from_backend, to_backend) {
+        return from_backend.copy_file(from, to, overwrite).await;
+    }
+
+    let mut reader = from_backend.open_read(from).await?;
+    let mut writer = to_backend.open_write(to, overwrite).await?;
+
+    tokio::io::copy(&mut reader, &mut writer)
+        .await
+        .context("copy_file_between", &[from, to])?;
+    writer
+        .shutdown()
+        .await
+        .context("copy_file_between", &[from, to])?;
     Ok(())
 }
@@ -1,11 +1,10 @@
 use std::path::{MAIN_SEPARATOR, MAIN_SEPARATOR_STR};
 
 fn rfind_utf8(s: &str, char: char) -> Option<usize> {
-    if let Some(rev_pos) = s.chars().rev().position(|c| c == char) {
-        Some(s.chars().count() - rev_pos - 1)
-    } else {
-        None
-    }
+    s.chars()
+        .rev()
+        .position(|c| c == char)
+        .map(|rev_pos| s.chars().count() - rev_pos - 1)
 }
 
 pub fn extract_lowest_path_item(path: &str) -> String {